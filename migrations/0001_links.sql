@@ -0,0 +1,10 @@
+-- Link records for the D1 storage backend (LINK_STORE = "d1")
+CREATE TABLE IF NOT EXISTS links (
+    id TEXT PRIMARY KEY,
+    record TEXT NOT NULL,
+    owner TEXT NOT NULL,
+    destination TEXT NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS links_owner_created_at ON links (owner, created_at);
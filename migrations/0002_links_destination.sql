@@ -0,0 +1,2 @@
+-- Lookups by destination for duplicate detection (DEDUP)
+CREATE INDEX IF NOT EXISTS links_destination_created_at ON links (destination, created_at);
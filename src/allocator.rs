@@ -0,0 +1,280 @@
+use worker::*;
+
+pub const BINDING: &str = "id_allocator";
+
+const RESERVED_KEY: &str = "reserved";
+const COUNTER_KEY: &str = "counter";
+const CLICKS_KEY: &str = "clicks";
+/// Followed by a [`crate::split::key`]; one click counter per A/B variant.
+const VARIANT_PREFIX: &str = "variant:";
+/// Opaque value held by `/claim`, and the epoch seconds it is held until.
+const CLAIM_KEY: &str = "claim";
+const CLAIM_UNTIL_KEY: &str = "claim_until";
+
+/// Object that hands out sequence numbers for the counter ID strategy; `_` never appears in short IDs.
+const COUNTER_OBJECT: &str = "__counter";
+/// First value handed out, so counter IDs are at least two base62 characters like custom names.
+const COUNTER_START: u64 = 62;
+
+/// Durable Object that owns a single short ID, named after the ID itself.
+///
+/// Requests to one object are serialized by the runtime, so the check-and-set in `/reserve` is
+/// atomic and two concurrent creates of the same name cannot both succeed. The same guarantee
+/// makes `/next` a gap-free counter and `/consume` an exact click budget. `/hit` and `/hits`
+/// count and report clicks per A/B variant, and `/reset` clears the counts for a reused name.
+///
+/// Objects whose name is not a short ID also serve `/claim`, `/settle` and `/unclaim`: a value
+/// held for a while, set only if nothing unexpired is held yet. Idempotency keys use these.
+#[durable_object]
+pub struct IdAllocator {
+    state: State,
+}
+
+impl DurableObject for IdAllocator {
+    fn new(state: State, _env: Env) -> Self {
+        Self { state }
+    }
+
+    async fn fetch(&self, req: Request) -> Result<Response> {
+        let storage = self.state.storage();
+        match (req.method(), req.path().as_str()) {
+            (Method::Post, "/reserve") => {
+                // A reservation lasts until `until` (epoch seconds), or forever when it is 0
+                let existing = storage.get_multiple(vec![RESERVED_KEY]).await?.get(&RESERVED_KEY.into());
+                let now = (Date::now().as_millis() / 1000) as f64;
+                let active = !existing.is_undefined()
+                    && existing.as_f64().is_none_or(|until| until == 0.0 || until > now);
+                if active {
+                    return Response::error("Conflict", 409);
+                }
+                let until = query_u64(&req, "until")?.unwrap_or(0);
+                storage.put(RESERVED_KEY, until).await?;
+                reset_counts(&storage).await?;
+                Ok(Response::empty()?.with_status(201))
+            },
+            (Method::Post, "/reset") => {
+                reset_counts(&storage).await?;
+                Ok(Response::empty()?.with_status(204))
+            },
+            (Method::Post, "/next") => {
+                let current = storage.get_multiple(vec![COUNTER_KEY]).await?
+                    .get(&COUNTER_KEY.into())
+                    .as_f64()
+                    .map(|n| n as u64);
+                let next = current.map_or(COUNTER_START, |n| n + 1);
+                storage.put(COUNTER_KEY, next).await?;
+                Response::ok(next.to_string())
+            },
+            (Method::Post, "/consume") => {
+                let limit = query_u64(&req, "limit")?.ok_or_else(|| Error::from("Missing limit"))?;
+                let clicks = storage.get_multiple(vec![CLICKS_KEY]).await?
+                    .get(&CLICKS_KEY.into())
+                    .as_f64()
+                    .map_or(0, |n| n as u64);
+                if clicks >= limit {
+                    return Response::error("Exhausted", 409);
+                }
+                storage.put(CLICKS_KEY, clicks + 1).await?;
+                Response::ok((limit - clicks - 1).to_string())
+            },
+            (Method::Post, "/hit") => {
+                let variant = query_str(&req, "variant")?.ok_or_else(|| Error::from("Missing variant"))?;
+                let key = format!("{}{}", VARIANT_PREFIX, variant);
+                let clicks = storage.get_multiple(vec![key.as_str()]).await?
+                    .get(&key.as_str().into())
+                    .as_f64()
+                    .map_or(0, |n| n as u64);
+                storage.put(&key, clicks + 1).await?;
+                Ok(Response::empty()?.with_status(204))
+            },
+            (Method::Post, "/hits") => {
+                // Comma-separated variant keys in, their click counts out, in the same order
+                let keys: Vec<String> = query_str(&req, "variants")?.unwrap_or_default()
+                    .split(',')
+                    .filter(|k| !k.is_empty())
+                    .map(|k| format!("{}{}", VARIANT_PREFIX, k))
+                    .collect();
+                let stored = if keys.is_empty() { js_sys::Map::new() } else { storage.get_multiple(keys.clone()).await? };
+                let counts: Vec<u64> = keys.iter()
+                    .map(|key| stored.get(&key.as_str().into()).as_f64().map_or(0, |n| n as u64))
+                    .collect();
+                Response::from_json(&counts)
+            },
+            (Method::Post, "/claim") => {
+                // Answers the value already held, or takes the request body as the new one
+                let stored = storage.get_multiple(vec![CLAIM_KEY, CLAIM_UNTIL_KEY]).await?;
+                let until = stored.get(&CLAIM_UNTIL_KEY.into()).as_f64().unwrap_or(0.0);
+                if until > (Date::now().as_millis() / 1000) as f64 {
+                    if let Some(value) = stored.get(&CLAIM_KEY.into()).as_string() {
+                        return Response::ok(value);
+                    }
+                }
+                hold(&storage, &req).await?;
+                Ok(Response::empty()?.with_status(201))
+            },
+            (Method::Post, "/settle") => {
+                hold(&storage, &req).await?;
+                Ok(Response::empty()?.with_status(204))
+            },
+            (Method::Post, "/unclaim") => {
+                storage.delete_multiple(vec![CLAIM_KEY, CLAIM_UNTIL_KEY]).await?;
+                Ok(Response::empty()?.with_status(204))
+            },
+            (Method::Post, "/release") => {
+                storage.delete(RESERVED_KEY).await?;
+                Ok(Response::empty()?.with_status(204))
+            },
+            _ => Response::error("Not Found", 404),
+        }
+    }
+
+    async fn alarm(&self) -> Result<Response> {
+        // Set for when the held value expires; later holds move it forward
+        self.state.storage().delete_multiple(vec![CLAIM_KEY, CLAIM_UNTIL_KEY]).await?;
+        Response::empty()
+    }
+}
+
+/// Hold the body of `req` for `ttl` seconds, and drop it from storage once that has passed.
+async fn hold(storage: &Storage, req: &Request) -> Result<()> {
+    let ttl = query_u64(req, "ttl")?.ok_or_else(|| Error::from("Missing ttl"))?;
+    let value = req.clone()?.text().await?;
+    let until = Date::now().as_millis() / 1000 + ttl;
+    storage.put(CLAIM_KEY, value).await?;
+    storage.put(CLAIM_UNTIL_KEY, until).await?;
+    storage.set_alarm(std::time::Duration::from_secs(ttl)).await
+}
+
+/// Forget the click budget and A/B counts, so a reused name starts from zero.
+async fn reset_counts(storage: &Storage) -> Result<()> {
+    storage.delete(CLICKS_KEY).await?;
+    let variants: Vec<String> = storage.list_with_options(ListOptions::new().prefix(VARIANT_PREFIX)).await?
+        .keys()
+        .into_iter()
+        .filter_map(|key| key.ok()?.as_string())
+        .collect();
+    if !variants.is_empty() {
+        storage.delete_multiple(variants).await?;
+    }
+    Ok(())
+}
+
+fn query_str(req: &Request, name: &str) -> Result<Option<String>> {
+    Ok(req.url()?
+        .query_pairs()
+        .find(|(k, _)| k == name)
+        .map(|(_, v)| v.into_owned()))
+}
+
+fn query_u64(req: &Request, name: &str) -> Result<Option<u64>> {
+    Ok(query_str(req, name)?.and_then(|v| v.parse().ok()))
+}
+
+/// Client side of [`IdAllocator`], bound to the `id_allocator` Durable Object namespace.
+pub struct Allocator {
+    namespace: ObjectNamespace,
+}
+
+impl Allocator {
+    pub fn new(namespace: ObjectNamespace) -> Self {
+        Self { namespace }
+    }
+
+    pub fn from_env(env: &Env) -> Result<Self> {
+        Ok(Self::new(env.durable_object(BINDING)?))
+    }
+
+    /// Atomically claim `id`, optionally only until `until` (epoch seconds) for links that KV
+    /// will expire. Returns `false` if it is already reserved.
+    pub async fn reserve(&self, id: &str, until: Option<u64>) -> Result<bool> {
+        let path = match until {
+            Some(until) => format!("/reserve?until={}", until),
+            None => "/reserve".to_string(),
+        };
+        let response = self.call(id, &path).await?;
+        match response.status_code() {
+            201 => Ok(true),
+            409 => Ok(false),
+            status => Err(format!("ID allocator returned unexpected status {}", status).into()),
+        }
+    }
+
+    pub async fn release(&self, id: &str) -> Result<()> {
+        self.call(id, "/release").await?;
+        Ok(())
+    }
+
+    /// Clear the click budget and A/B counts of `id`, for a link just stored under that name.
+    pub async fn reset(&self, id: &str) -> Result<()> {
+        self.call(id, "/reset").await?;
+        Ok(())
+    }
+
+    /// Count one use of a click-limited link. Returns `false` once `limit` uses have been consumed.
+    pub async fn consume(&self, id: &str, limit: u64) -> Result<bool> {
+        let response = self.call(id, &format!("/consume?limit={}", limit)).await?;
+        match response.status_code() {
+            200 => Ok(true),
+            409 => Ok(false),
+            status => Err(format!("ID allocator returned unexpected status {}", status).into()),
+        }
+    }
+
+    /// Count a click that was sent to the A/B variant with key `variant`.
+    pub async fn record_variant(&self, id: &str, variant: &str) -> Result<()> {
+        self.call(id, &format!("/hit?variant={}", variant)).await?;
+        Ok(())
+    }
+
+    /// Clicks counted for each of `variants` (A/B variant keys), in the same order.
+    pub async fn variant_clicks(&self, id: &str, variants: &[String]) -> Result<Vec<u64>> {
+        let mut response = self.call(id, &format!("/hits?variants={}", variants.join(","))).await?;
+        response.json().await
+    }
+
+    /// Hold `value` under `name` for `ttl_secs` unless an unexpired value is held already.
+    /// Returns that earlier value, or `None` when `value` is now held.
+    pub async fn claim(&self, name: &str, value: &str, ttl_secs: u64) -> Result<Option<String>> {
+        let mut response = self.send(name, &format!("/claim?ttl={}", ttl_secs), Some(value)).await?;
+        match response.status_code() {
+            201 => Ok(None),
+            200 => Ok(Some(response.text().await?)),
+            status => Err(format!("ID allocator returned {} for a claim", status).into()),
+        }
+    }
+
+    /// Replace the value held under `name`, holding it for `ttl_secs` from now.
+    pub async fn settle(&self, name: &str, value: &str, ttl_secs: u64) -> Result<()> {
+        self.send(name, &format!("/settle?ttl={}", ttl_secs), Some(value)).await?;
+        Ok(())
+    }
+
+    /// Drop whatever is held under `name`.
+    pub async fn unclaim(&self, name: &str) -> Result<()> {
+        self.call(name, "/unclaim").await?;
+        Ok(())
+    }
+
+    /// Next value of the shared sequence used by the counter ID strategy.
+    pub async fn next_counter(&self) -> Result<u64> {
+        let mut response = self.call(COUNTER_OBJECT, "/next").await?;
+        response.text().await?
+            .parse()
+            .map_err(|_| "ID allocator returned an invalid counter".into())
+    }
+
+    async fn call(&self, id: &str, path: &str) -> Result<Response> {
+        self.send(id, path, None).await
+    }
+
+    async fn send(&self, id: &str, path: &str, body: Option<&str>) -> Result<Response> {
+        let stub = self.namespace.id_from_name(id)?.get_stub()?;
+        let mut init = RequestInit::new();
+        init.with_method(Method::Post);
+        if let Some(body) = body {
+            init.with_body(Some(body.into()));
+        }
+        let req = Request::new_with_init(&format!("https://allocator{}", path), &init)?;
+        stub.fetch_with_request(req).await
+    }
+}
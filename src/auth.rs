@@ -0,0 +1,134 @@
+use worker::{Date, Env, Request, Result};
+
+use crate::config;
+
+pub mod access;
+pub mod roles;
+pub mod tokens;
+
+pub use roles::{Permission, Role};
+use tokens::Scope;
+
+/// The signed-in user making a request.
+#[derive(Debug, Clone)]
+pub struct User {
+    pub email: String,
+    pub groups: Vec<String>,
+    pub role: Role,
+    /// Scopes of the API token the request was made with; `None` for browser sessions.
+    pub scopes: Option<Vec<Scope>>,
+}
+
+impl User {
+    fn new(env: &Env, email: String, groups: Vec<String>) -> Self {
+        let role = Role::resolve(env, &email, &groups);
+        Self { email, groups, role, scopes: None }
+    }
+
+    pub fn can(&self, permission: Permission) -> bool {
+        self.role.allows(permission)
+            && self.scopes.as_ref().is_none_or(|scopes| scopes.contains(&Scope::for_permission(permission)))
+    }
+
+    /// Whether the user may change `owner`'s link: their own with `ManageOwnLinks`, anyone's with `ManageAnyLink`.
+    pub fn can_manage(&self, owner: &str) -> bool {
+        (owner.eq_ignore_ascii_case(&self.email) && self.can(Permission::ManageOwnLinks))
+            || self.can(Permission::ManageAnyLink)
+    }
+
+    /// Whether link listings show everyone's links: always for viewers, on request for admins.
+    /// Decided by role alone, so a token without `links:write` does not widen the listing.
+    pub fn lists_all_links(&self, requested: bool) -> bool {
+        !self.role.allows(Permission::CreateLinks) || (requested && self.role.allows(Permission::ManageAnyLink))
+    }
+}
+
+/// Why a request could not be authenticated; handlers turn this into a 401/403 page.
+#[derive(Debug, Clone)]
+pub struct AuthError {
+    pub status: u16,
+    pub message: &'static str,
+}
+
+impl AuthError {
+    fn unauthorized(message: &'static str) -> Self {
+        Self { status: 401, message }
+    }
+
+    fn forbidden(message: &'static str) -> Self {
+        Self { status: 403, message }
+    }
+}
+
+/// Identify the caller from an `Authorization: Bearer` API token if one is sent, otherwise
+/// from the Cloudflare Access JWT in `Cf-Access-Jwt-Assertion`.
+///
+/// When neither is present and `AUTH_DEV_EMAIL` is set (intended for `.dev.vars` under
+/// `wrangler dev` only), the request is treated as coming from that address instead.
+pub async fn authenticate(req: &Request, env: &Env) -> Result<std::result::Result<User, AuthError>> {
+    let bearer = req.headers().get("authorization")?
+        .and_then(|h| h.strip_prefix("Bearer ").map(|t| t.trim().to_string()));
+    if let Some(bearer) = bearer {
+        return tokens::authenticate(env, &bearer, Date::now().as_millis()).await;
+    }
+
+    let token = req.headers().get("cf-access-jwt-assertion")?.filter(|t| !t.is_empty());
+
+    let Some(token) = token else {
+        return Ok(match config::var(env, "AUTH_DEV_EMAIL") {
+            Some(email) => Ok(User::new(env, email, config::list(env, "AUTH_DEV_GROUPS"))),
+            None => Err(AuthError::unauthorized("Sign in through Cloudflare Access to continue.")),
+        });
+    };
+
+    let Some(access) = access::AccessConfig::from_env(env) else {
+        return Ok(Err(AuthError::forbidden("Cloudflare Access is not configured (ACCESS_TEAM_DOMAIN / ACCESS_AUD).")));
+    };
+
+    let now = Date::now().as_millis() / 1000;
+    Ok(match access::verify(&token, &access, now).await? {
+        Ok(claims) => {
+            let groups = claims.groups(&config::var(env, "ACCESS_GROUPS_CLAIM").unwrap_or_else(|| "groups".to_string()));
+            match claims.email {
+                Some(email) if !email.is_empty() => Ok(User::new(env, email, groups)),
+                _ => Err(AuthError::forbidden("Access token does not identify a user")),
+            }
+        },
+        Err(reason) => Err(AuthError::forbidden(reason)),
+    })
+}
+
+/// [`authenticate`], then require `permission` of the user's role.
+pub async fn require(req: &Request, env: &Env, permission: Permission) -> Result<std::result::Result<User, AuthError>> {
+    Ok(match authenticate(req, env).await? {
+        Ok(user) if user.can(permission) => Ok(user),
+        Ok(user) if user.role.allows(permission) => Err(AuthError::forbidden("This API token lacks the scope for this action.")),
+        Ok(_) => Err(AuthError::forbidden("Your role does not allow this action.")),
+        Err(error) => Err(error),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(role: Role) -> User {
+        User { email: "Ann@Example.com".into(), groups: Vec::new(), role, scopes: None }
+    }
+
+    #[test]
+    fn can_manage_checks_ownership_case_insensitively() {
+        assert!(user(Role::Creator).can_manage("ann@example.COM"));
+        assert!(!user(Role::Creator).can_manage("bob@example.com"));
+        assert!(!user(Role::Viewer).can_manage("ann@example.com"));
+        assert!(user(Role::Admin).can_manage("bob@example.com"));
+    }
+
+    #[test]
+    fn lists_all_links_for_viewers_and_admins_on_request() {
+        assert!(user(Role::Viewer).lists_all_links(false));
+        assert!(!user(Role::Creator).lists_all_links(true));
+        assert!(!user(Role::Admin).lists_all_links(false));
+        assert!(user(Role::Admin).lists_all_links(true));
+    }
+}
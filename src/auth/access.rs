@@ -0,0 +1,264 @@
+use std::cell::RefCell;
+
+use rsa::{BigUint, Pkcs1v15Sign, RsaPublicKey};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use worker::{Env, Fetch, Result, Url};
+
+use crate::config;
+use crate::crypto;
+
+/// Keys are refreshed at most this often, and also early when a token names an unknown `kid`.
+const JWKS_TTL_SECS: u64 = 60 * 60;
+const JWKS_MIN_REFRESH_SECS: u64 = 60;
+/// Clock skew tolerated on `exp`/`nbf`.
+const LEEWAY_SECS: u64 = 60;
+
+/// Where to find and how to validate Cloudflare Access tokens, from `ACCESS_TEAM_DOMAIN` and `ACCESS_AUD`.
+pub struct AccessConfig {
+    issuer: String,
+    audience: String,
+}
+
+impl AccessConfig {
+    pub fn from_env(env: &Env) -> Option<Self> {
+        Some(Self::new(&config::var(env, "ACCESS_TEAM_DOMAIN")?, config::var(env, "ACCESS_AUD")?))
+    }
+
+    pub fn new(team: &str, audience: String) -> Self {
+        let host = team.trim_start_matches("https://").trim_end_matches('/');
+        Self {
+            issuer: format!("https://{}", host),
+            audience,
+        }
+    }
+
+    fn certs_url(&self) -> String {
+        format!("{}/cdn-cgi/access/certs", self.issuer)
+    }
+}
+
+#[derive(Deserialize)]
+struct Header {
+    alg: String,
+    kid: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Audience {
+    fn contains(&self, audience: &str) -> bool {
+        match self {
+            Self::One(aud) => aud == audience,
+            Self::Many(auds) => auds.iter().any(|aud| aud == audience),
+        }
+    }
+}
+
+/// Claims read from a verified Access token.
+#[derive(Deserialize)]
+pub struct Claims {
+    aud: Audience,
+    iss: String,
+    exp: u64,
+    #[serde(default)]
+    nbf: Option<u64>,
+    #[serde(default)]
+    pub email: Option<String>,
+    /// Every other claim, so group claims can be looked up by configured name.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Claims {
+    /// Group names from the claim called `name`, which may hold a list or a single string.
+    pub fn groups(&self, name: &str) -> Vec<String> {
+        match self.extra.get(name) {
+            Some(serde_json::Value::Array(values)) => values.iter().filter_map(|v| v.as_str().map(String::from)).collect(),
+            Some(serde_json::Value::String(value)) => vec![value.clone()],
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+struct Jwk {
+    kid: String,
+    n: String,
+    e: String,
+}
+
+#[derive(Deserialize)]
+struct Jwks {
+    keys: Vec<Jwk>,
+}
+
+struct CachedKeys {
+    issuer: String,
+    fetched_at: u64,
+    keys: Vec<Jwk>,
+}
+
+thread_local! {
+    // Isolates are single-threaded and reused across requests, so this caches keys between them
+    static JWKS_CACHE: RefCell<Option<CachedKeys>> = const { RefCell::new(None) };
+}
+
+/// Verify an Access JWT. The outer `Result` is for infrastructure failures (fetching keys),
+/// the inner one describes why an otherwise well-formed request is rejected.
+pub async fn verify(token: &str, config: &AccessConfig, now: u64) -> Result<std::result::Result<Claims, &'static str>> {
+    let mut parts = token.split('.');
+    let (Some(header_b64), Some(payload_b64), Some(signature_b64), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+        return Ok(Err("Malformed access token"));
+    };
+
+    let header: Header = match crypto::decode(header_b64).and_then(|h| serde_json::from_slice(&h).ok()) {
+        Some(header) => header,
+        None => return Ok(Err("Malformed access token")),
+    };
+    if header.alg != "RS256" {
+        return Ok(Err("Unsupported access token algorithm"));
+    }
+    let Some(kid) = header.kid else {
+        return Ok(Err("Access token has no key ID"));
+    };
+    let Some(signature) = crypto::decode(signature_b64) else {
+        return Ok(Err("Malformed access token"));
+    };
+
+    let Some(jwk) = find_key(config, &kid, now).await? else {
+        return Ok(Err("Access token signed by an unknown key"));
+    };
+    let key = match (crypto::decode(&jwk.n), crypto::decode(&jwk.e)) {
+        (Some(n), Some(e)) => RsaPublicKey::new(BigUint::from_bytes_be(&n), BigUint::from_bytes_be(&e))
+            .map_err(|e| format!("Invalid Access signing key: {}", e))?,
+        _ => return Err("Invalid Access signing key".into()),
+    };
+    let digest = Sha256::digest(format!("{}.{}", header_b64, payload_b64).as_bytes());
+    if key.verify(Pkcs1v15Sign::new::<Sha256>(), &digest, &signature).is_err() {
+        return Ok(Err("Invalid access token signature"));
+    }
+
+    let claims: Claims = match crypto::decode(payload_b64).and_then(|p| serde_json::from_slice(&p).ok()) {
+        Some(claims) => claims,
+        None => return Ok(Err("Malformed access token")),
+    };
+    Ok(validate_claims(&claims, config, now).map(|()| claims))
+}
+
+/// Check issuer, audience and validity window of signature-verified `claims` at `now` (epoch
+/// seconds), allowing [`LEEWAY_SECS`] of clock skew either way.
+fn validate_claims(claims: &Claims, config: &AccessConfig, now: u64) -> std::result::Result<(), &'static str> {
+    if claims.iss != config.issuer {
+        return Err("Access token issued by an unexpected team");
+    }
+    if !claims.aud.contains(&config.audience) {
+        return Err("Access token issued for a different application");
+    }
+    if claims.exp.saturating_add(LEEWAY_SECS) < now {
+        return Err("Access token has expired");
+    }
+    if claims.nbf.is_some_and(|nbf| nbf > now.saturating_add(LEEWAY_SECS)) {
+        return Err("Access token is not valid yet");
+    }
+    Ok(())
+}
+
+async fn find_key(config: &AccessConfig, kid: &str, now: u64) -> Result<Option<Jwk>> {
+    let lookup = |refresh_after: u64| JWKS_CACHE.with(|cache| {
+        let cache = cache.borrow();
+        let cached = cache.as_ref().filter(|c| c.issuer == config.issuer)?;
+        let key = cached.keys.iter().find(|k| k.kid == kid).cloned();
+        // Fresh enough to trust, or too recent to refetch just because the kid is unknown
+        if key.is_some() && now < cached.fetched_at + refresh_after {
+            return Some(key);
+        }
+        if key.is_none() && now < cached.fetched_at + JWKS_MIN_REFRESH_SECS {
+            return Some(None);
+        }
+        None
+    });
+
+    if let Some(key) = lookup(JWKS_TTL_SECS) {
+        return Ok(key);
+    }
+
+    let mut response = Fetch::Url(Url::parse(&config.certs_url())?).send().await?;
+    if response.status_code() != 200 {
+        return Err(format!("Fetching Access certificates failed with status {}", response.status_code()).into());
+    }
+    let jwks: Jwks = response.json().await?;
+    let key = jwks.keys.iter().find(|k| k.kid == kid).cloned();
+    JWKS_CACHE.with(|cache| {
+        *cache.borrow_mut() = Some(CachedKeys {
+            issuer: config.issuer.clone(),
+            fetched_at: now,
+            keys: jwks.keys,
+        });
+    });
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn config() -> AccessConfig {
+        AccessConfig::new("https://team.cloudflareaccess.com/", "app-aud".to_string())
+    }
+
+    fn claims(json: serde_json::Value) -> Claims {
+        let mut claims = serde_json::json!({
+            "aud": ["app-aud"],
+            "iss": "https://team.cloudflareaccess.com",
+            "exp": NOW + 600,
+            "nbf": NOW - 10,
+            "email": "ann@example.com",
+        });
+        claims.as_object_mut().unwrap().extend(json.as_object().unwrap().clone());
+        serde_json::from_value(claims).unwrap()
+    }
+
+    #[test]
+    fn validate_claims_accepts_a_current_token() {
+        assert_eq!(validate_claims(&claims(serde_json::json!({})), &config(), NOW), Ok(()));
+        assert_eq!(validate_claims(&claims(serde_json::json!({"aud": "app-aud"})), &config(), NOW), Ok(()));
+        assert_eq!(config().certs_url(), "https://team.cloudflareaccess.com/cdn-cgi/access/certs");
+    }
+
+    #[test]
+    fn validate_claims_rejects_other_teams_and_applications() {
+        let wrong_issuer = claims(serde_json::json!({"iss": "https://other.cloudflareaccess.com"}));
+        assert_eq!(validate_claims(&wrong_issuer, &config(), NOW), Err("Access token issued by an unexpected team"));
+        let wrong_audience = claims(serde_json::json!({"aud": ["other-aud"]}));
+        assert_eq!(validate_claims(&wrong_audience, &config(), NOW), Err("Access token issued for a different application"));
+    }
+
+    #[test]
+    fn validate_claims_checks_the_validity_window_with_leeway() {
+        let expired = claims(serde_json::json!({"exp": NOW - 100}));
+        assert_eq!(validate_claims(&expired, &config(), NOW), Err("Access token has expired"));
+        let just_expired = claims(serde_json::json!({"exp": NOW - LEEWAY_SECS}));
+        assert_eq!(validate_claims(&just_expired, &config(), NOW), Ok(()));
+
+        let future = claims(serde_json::json!({"nbf": NOW + 100}));
+        assert_eq!(validate_claims(&future, &config(), NOW), Err("Access token is not valid yet"));
+        let almost_valid = claims(serde_json::json!({"nbf": NOW + LEEWAY_SECS}));
+        assert_eq!(validate_claims(&almost_valid, &config(), NOW), Ok(()));
+    }
+
+    #[test]
+    fn groups_reads_lists_and_single_values() {
+        let claims = claims(serde_json::json!({"groups": ["eng", "ops"], "team": "eng"}));
+        assert_eq!(claims.groups("groups"), ["eng", "ops"]);
+        assert_eq!(claims.groups("team"), ["eng"]);
+        assert!(claims.groups("missing").is_empty());
+    }
+}
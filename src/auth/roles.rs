@@ -0,0 +1,145 @@
+use worker::Env;
+
+use crate::config;
+
+/// What a signed-in user may do, from least to most privileged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    /// Read-only: browse every link and its QR code, but create or change nothing.
+    Viewer,
+    /// Create links and manage the ones they created.
+    Creator,
+    /// Manage any link and register reserved names.
+    Admin,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    ReadLinks,
+    ViewStats,
+    CreateLinks,
+    ManageOwnLinks,
+    ManageAnyLink,
+    UseReservedNames,
+}
+
+impl Role {
+    pub fn allows(self, permission: Permission) -> bool {
+        let required = match permission {
+            Permission::ReadLinks | Permission::ViewStats => Role::Viewer,
+            Permission::CreateLinks | Permission::ManageOwnLinks => Role::Creator,
+            Permission::ManageAnyLink | Permission::UseReservedNames => Role::Admin,
+        };
+        self >= required
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "viewer" => Some(Self::Viewer),
+            "creator" => Some(Self::Creator),
+            "admin" => Some(Self::Admin),
+            _ => None,
+        }
+    }
+
+    /// Resolve a user's role from the deployment's [`RoleRules`].
+    pub fn resolve(env: &Env, email: &str, groups: &[String]) -> Self {
+        RoleRules::from_env(env).resolve(email, groups)
+    }
+}
+
+/// Who gets which role: `ADMIN_EMAILS`/`ADMIN_GROUPS`, then `CREATOR_EMAILS`/`CREATOR_GROUPS`,
+/// falling back to `DEFAULT_ROLE` (`creator` unless configured otherwise).
+#[derive(Debug, Clone)]
+pub struct RoleRules {
+    pub admin_emails: Vec<String>,
+    pub admin_groups: Vec<String>,
+    pub creator_emails: Vec<String>,
+    pub creator_groups: Vec<String>,
+    pub default: Role,
+}
+
+impl RoleRules {
+    pub fn from_env(env: &Env) -> Self {
+        Self {
+            admin_emails: config::list(env, "ADMIN_EMAILS"),
+            admin_groups: config::list(env, "ADMIN_GROUPS"),
+            creator_emails: config::list(env, "CREATOR_EMAILS"),
+            creator_groups: config::list(env, "CREATOR_GROUPS"),
+            default: config::var(env, "DEFAULT_ROLE").and_then(|r| Role::parse(&r)).unwrap_or(Role::Creator),
+        }
+    }
+
+    /// Emails match case-insensitively, group names exactly.
+    pub fn resolve(&self, email: &str, groups: &[String]) -> Role {
+        let matches = |emails: &[String], role_groups: &[String]| {
+            emails.iter().any(|e| e.eq_ignore_ascii_case(email))
+                || role_groups.iter().any(|g| groups.iter().any(|ug| ug == g))
+        };
+
+        if matches(&self.admin_emails, &self.admin_groups) {
+            Role::Admin
+        } else if matches(&self.creator_emails, &self.creator_groups) {
+            Role::Creator
+        } else {
+            self.default
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERMISSIONS: [Permission; 6] = [
+        Permission::ReadLinks,
+        Permission::ViewStats,
+        Permission::CreateLinks,
+        Permission::ManageOwnLinks,
+        Permission::ManageAnyLink,
+        Permission::UseReservedNames,
+    ];
+
+    #[test]
+    fn allows_follows_the_permission_matrix() {
+        let table = [
+            (Role::Viewer, [true, true, false, false, false, false]),
+            (Role::Creator, [true, true, true, true, false, false]),
+            (Role::Admin, [true, true, true, true, true, true]),
+        ];
+        for (role, allowed) in table {
+            for (permission, expected) in PERMISSIONS.into_iter().zip(allowed) {
+                assert_eq!(role.allows(permission), expected, "{:?} {:?}", role, permission);
+            }
+        }
+    }
+
+    fn rules() -> RoleRules {
+        RoleRules {
+            admin_emails: vec!["Boss@Example.com".into()],
+            admin_groups: vec!["admins".into()],
+            creator_emails: vec!["writer@example.com".into()],
+            creator_groups: vec!["staff".into()],
+            default: Role::Viewer,
+        }
+    }
+
+    #[test]
+    fn resolve_checks_admins_then_creators_then_the_default() {
+        let rules = rules();
+        let groups = |names: &[&str]| names.iter().map(|g| g.to_string()).collect::<Vec<_>>();
+        assert_eq!(rules.resolve("boss@example.COM", &[]), Role::Admin);
+        assert_eq!(rules.resolve("ann@example.com", &groups(&["staff", "admins"])), Role::Admin);
+        assert_eq!(rules.resolve("WRITER@example.com", &[]), Role::Creator);
+        assert_eq!(rules.resolve("ann@example.com", &groups(&["staff"])), Role::Creator);
+        assert_eq!(rules.resolve("ann@example.com", &groups(&["Staff"])), Role::Viewer);
+        assert_eq!(rules.resolve("ann@example.com", &[]), Role::Viewer);
+    }
+
+    #[test]
+    fn parse_reads_role_names() {
+        assert_eq!(Role::parse("Admin"), Some(Role::Admin));
+        assert_eq!(Role::parse("viewer"), Some(Role::Viewer));
+        assert_eq!(Role::parse("owner"), None);
+    }
+}
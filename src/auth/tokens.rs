@@ -0,0 +1,267 @@
+use serde::{Deserialize, Serialize};
+use worker::{kv::KvStore, Env, Result};
+
+use super::{AuthError, Permission, User};
+use crate::crypto;
+use crate::store::kv;
+
+/// Every token starts with this, so leaked tokens are easy to recognise and grep for.
+pub const TOKEN_PREFIX: &str = "elk_";
+const KEY_PREFIX: &str = "token:";
+/// `last_used_at` is only rewritten once it is this stale, to keep KV writes off the hot path.
+const LAST_USED_RESOLUTION_MS: u64 = 60 * 1000;
+/// Expired tokens stay listed (as expired) this long before KV drops them.
+const EXPIRED_RETENTION_SECS: u64 = 7 * 24 * 60 * 60;
+pub const MAX_NAME_LENGTH: usize = 64;
+
+/// What a token may be used for, on top of what its owner's role allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Scope {
+    #[serde(rename = "links:read")]
+    LinksRead,
+    #[serde(rename = "links:write")]
+    LinksWrite,
+    #[serde(rename = "stats:read")]
+    StatsRead,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 3] = [Scope::LinksRead, Scope::LinksWrite, Scope::StatsRead];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::LinksRead => "links:read",
+            Scope::LinksWrite => "links:write",
+            Scope::StatsRead => "stats:read",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scope| scope.as_str() == value)
+    }
+
+    /// The scope a token needs to exercise `permission`.
+    pub fn for_permission(permission: Permission) -> Self {
+        match permission {
+            Permission::ReadLinks => Scope::LinksRead,
+            Permission::ViewStats => Scope::StatsRead,
+            Permission::CreateLinks
+            | Permission::ManageOwnLinks
+            | Permission::ManageAnyLink
+            | Permission::UseReservedNames => Scope::LinksWrite,
+        }
+    }
+}
+
+/// A personal API token as stored in KV under `token:<id>`. Only a digest of the secret is kept.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: String,
+    pub name: String,
+    pub owner: String,
+    /// Access groups of the owner when the token was minted, so group-based roles carry over.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+    pub scopes: Vec<Scope>,
+    pub hash: String,
+    pub created_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<u64>,
+}
+
+impl ApiToken {
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|at| at <= now)
+    }
+
+    /// Whether `secret` is this token's secret and the token is still valid at `now`.
+    fn accepts(&self, secret: &str, now: u64) -> bool {
+        crypto::verify_digest(secret, &self.hash) && !self.is_expired(now)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct TokenMetadata {
+    owner: String,
+}
+
+/// Split `elk_<id>.<secret>` into its ID and secret.
+fn parse(token: &str) -> Option<(&str, &str)> {
+    let (id, secret) = token.strip_prefix(TOKEN_PREFIX)?.split_once('.')?;
+    (!id.is_empty() && !secret.is_empty()).then_some((id, secret))
+}
+
+/// API tokens, kept in the links KV namespace under the `token:` prefix.
+pub struct TokenStore {
+    kv: KvStore,
+}
+
+impl TokenStore {
+    pub fn from_env(env: &Env) -> Result<Self> {
+        Ok(Self { kv: env.kv(kv::BINDING)? })
+    }
+
+    async fn write(&self, token: &ApiToken) -> Result<()> {
+        let mut put = self.kv.put(&format!("{}{}", KEY_PREFIX, token.id), serde_json::to_string(token)?)?
+            .metadata(TokenMetadata { owner: token.owner.clone() })?;
+        if let Some(at) = token.expires_at {
+            put = put.expiration(at / 1000 + EXPIRED_RETENTION_SECS);
+        }
+        put.execute().await?;
+        Ok(())
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<ApiToken>> {
+        Ok(self.kv.get(&format!("{}{}", KEY_PREFIX, id)).json().await?)
+    }
+
+    /// Create a token for `user` and return the secret token string, which is never stored.
+    pub async fn mint(&self, user: &User, name: &str, scopes: Vec<Scope>, expires_at: Option<u64>, now: u64) -> Result<(String, ApiToken)> {
+        let id = crypto::encode(&crypto::random_bytes::<9>()?);
+        let secret = crypto::encode(&crypto::random_bytes::<32>()?);
+        let token = ApiToken {
+            id,
+            name: name.to_string(),
+            owner: user.email.clone(),
+            groups: user.groups.clone(),
+            scopes,
+            hash: crypto::digest(&secret),
+            created_at: now,
+            expires_at,
+            last_used_at: None,
+        };
+        self.write(&token).await?;
+        Ok((format!("{}{}.{}", TOKEN_PREFIX, token.id, secret), token))
+    }
+
+    /// Every token owned by `owner`, newest first.
+    pub async fn list(&self, owner: &str) -> Result<Vec<ApiToken>> {
+        let mut ids = Vec::new();
+        let mut cursor = None;
+        loop {
+            let mut request = self.kv.list().prefix(KEY_PREFIX.to_string());
+            if let Some(cursor) = cursor {
+                request = request.cursor(cursor);
+            }
+            let response = request.execute().await?;
+            ids.extend(response.keys.into_iter()
+                .filter(|key| key.metadata.clone()
+                    .and_then(|m| serde_json::from_value::<TokenMetadata>(m).ok())
+                    .is_some_and(|m| m.owner.eq_ignore_ascii_case(owner)))
+                .map(|key| key.name[KEY_PREFIX.len()..].to_string()));
+            if response.list_complete {
+                break;
+            }
+            cursor = response.cursor;
+        }
+
+        let mut tokens = Vec::new();
+        for id in ids {
+            tokens.extend(self.get(&id).await?);
+        }
+        tokens.sort_by_key(|t| std::cmp::Reverse(t.created_at));
+        Ok(tokens)
+    }
+
+    /// Delete `owner`'s token `id`. Returns `false` if there is no such token.
+    pub async fn revoke(&self, owner: &str, id: &str) -> Result<bool> {
+        match self.get(id).await? {
+            Some(token) if token.owner.eq_ignore_ascii_case(owner) => {
+                self.kv.delete(&format!("{}{}", KEY_PREFIX, id)).await?;
+                Ok(true)
+            },
+            _ => Ok(false),
+        }
+    }
+
+    /// Look up a presented token, checking its secret and expiry, and record that it was used.
+    pub async fn verify(&self, presented: &str, now: u64) -> Result<Option<ApiToken>> {
+        let Some((id, secret)) = parse(presented) else {
+            return Ok(None);
+        };
+        let Some(mut token) = self.get(id).await? else {
+            return Ok(None);
+        };
+        if !token.accepts(secret, now) {
+            return Ok(None);
+        }
+        if token.last_used_at.is_none_or(|at| now.saturating_sub(at) >= LAST_USED_RESOLUTION_MS) {
+            token.last_used_at = Some(now);
+            self.write(&token).await?;
+        }
+        Ok(Some(token))
+    }
+}
+
+/// Identify the caller from an `Authorization: Bearer` API token.
+pub async fn authenticate(env: &Env, presented: &str, now: u64) -> Result<std::result::Result<User, AuthError>> {
+    Ok(match TokenStore::from_env(env)?.verify(presented, now).await? {
+        Some(token) => {
+            let mut user = User::new(env, token.owner, token.groups);
+            user.scopes = Some(token.scopes);
+            Ok(user)
+        },
+        None => Err(AuthError::unauthorized("API token is invalid, expired or revoked.")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Role;
+
+    #[test]
+    fn parse_splits_id_and_secret() {
+        assert_eq!(parse("elk_abc.s3cret"), Some(("abc", "s3cret")));
+        for malformed in ["", "elk_", "elk_abc", "elk_.s3cret", "elk_abc.", "abc.s3cret", "ELK_abc.s3cret", "xelk_abc.s3cret"] {
+            assert_eq!(parse(malformed), None, "{}", malformed);
+        }
+    }
+
+    #[test]
+    fn scopes_map_to_permissions() {
+        assert_eq!(Scope::for_permission(Permission::ReadLinks), Scope::LinksRead);
+        assert_eq!(Scope::for_permission(Permission::ViewStats), Scope::StatsRead);
+        for permission in [Permission::CreateLinks, Permission::ManageOwnLinks, Permission::ManageAnyLink, Permission::UseReservedNames] {
+            assert_eq!(Scope::for_permission(permission), Scope::LinksWrite);
+        }
+        for scope in Scope::ALL {
+            assert_eq!(Scope::parse(scope.as_str()), Some(scope));
+        }
+        assert_eq!(Scope::parse("links:admin"), None);
+    }
+
+    #[test]
+    fn read_only_tokens_cannot_write() {
+        let user = User { email: "ann@example.com".into(), groups: Vec::new(), role: Role::Admin, scopes: Some(vec![Scope::LinksRead]) };
+        assert!(user.can(Permission::ReadLinks));
+        assert!(!user.can(Permission::ViewStats));
+        assert!(!user.can(Permission::CreateLinks));
+        assert!(!user.can_manage("ann@example.com"));
+
+        // Scopes never add to what the role allows
+        let viewer = User { role: Role::Viewer, scopes: Some(Scope::ALL.to_vec()), ..user };
+        assert!(!viewer.can(Permission::CreateLinks));
+    }
+
+    #[test]
+    fn accepts_checks_secret_and_expiry() {
+        let token = ApiToken {
+            id: "abc".into(),
+            name: "ci".into(),
+            owner: "ann@example.com".into(),
+            groups: Vec::new(),
+            scopes: vec![Scope::LinksRead],
+            hash: crypto::digest("s3cret"),
+            created_at: 1000,
+            expires_at: Some(5000),
+            last_used_at: None,
+        };
+        assert!(token.accepts("s3cret", 4999));
+        assert!(!token.accepts("s3cret", 5000));
+        assert!(!token.accepts("wrong", 4999));
+        assert!(ApiToken { expires_at: None, ..token }.accepts("s3cret", u64::MAX));
+    }
+}
@@ -0,0 +1,35 @@
+use worker::Env;
+
+use crate::link::{DEFAULT_STATUS, REDIRECT_STATUSES};
+
+/// Read a wrangler `[vars]` entry, treating missing and blank values the same.
+pub fn var(env: &Env, name: &str) -> Option<String> {
+    env.var(name)
+        .ok()
+        .map(|v| v.to_string().trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// Read a numeric var, falling back to `default` when it is missing or malformed.
+pub fn var_or<T: std::str::FromStr>(env: &Env, name: &str, default: T) -> T {
+    var(env, name).and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+/// Read a comma-separated var as a list of trimmed, non-empty entries.
+pub fn list(env: &Env, name: &str) -> Vec<String> {
+    var(env, name)
+        .map(|v| v.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+/// How long soft-deleted links can be restored, from `DELETE_RETENTION_DAYS` (default 30).
+pub fn delete_retention_ms(env: &Env) -> u64 {
+    var_or(env, "DELETE_RETENTION_DAYS", 30u64) * 24 * 60 * 60 * 1000
+}
+
+/// Status used by links created without one, from `REDIRECT_STATUS` (default 302).
+pub fn redirect_status(env: &Env) -> u16 {
+    Some(var_or(env, "REDIRECT_STATUS", DEFAULT_STATUS))
+        .filter(|status| REDIRECT_STATUSES.contains(status))
+        .unwrap_or(DEFAULT_STATUS)
+}
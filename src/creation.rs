@@ -0,0 +1,404 @@
+use worker::{Env, Result};
+
+use crate::allocator::Allocator;
+use crate::auth::{Permission, User};
+use crate::config;
+use crate::crypto::PasswordHash;
+use crate::destination;
+use crate::device;
+use crate::expiry;
+use crate::geo;
+use crate::id::IdStrategy;
+use crate::link::{DeviceRule, GeoRule, LinkRecord, QueryPolicy, Variant, REDIRECT_STATUSES};
+use crate::reserved::{Reservation, ReservedNames};
+use crate::split;
+use crate::store::LinkStore;
+
+/// Give up after this many consecutive ID collisions rather than looping forever.
+const MAX_ID_ATTEMPTS: u32 = 8;
+
+/// A create request as submitted by the HTML form or the JSON API, before validation.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct NewLink {
+    pub url: String,
+    pub name: Option<String>,
+    /// Duration (`7d`, `12h`) or UTC date, as accepted by [`expiry::parse`].
+    pub expires: Option<String>,
+    pub max_clicks: Option<u64>,
+    pub password: Option<String>,
+    /// Register a name on the `RESERVED_NAMES` deny list; ignored unless the user may.
+    pub allow_reserved: bool,
+    /// One of [`REDIRECT_STATUSES`]; the deployment's `REDIRECT_STATUS` when absent.
+    pub status: Option<u16>,
+    #[serde(default)]
+    pub query: QueryPolicy,
+    /// Also forward `/<name>/<rest>` with `<rest>` appended to the destination path.
+    pub prefix: bool,
+    /// Country and continent specific destinations; see [`geo::select`].
+    pub geo: Vec<GeoRule>,
+    /// Platform specific destinations; see [`device::select`].
+    pub devices: Vec<DeviceRule>,
+    /// Weighted A/B destinations; see [`split::validate`].
+    pub variants: Vec<Variant>,
+    /// Pin each visitor to the first A/B variant they were sent to.
+    pub sticky: bool,
+}
+
+/// Why a link was not created, with the HTTP status and machine-readable code the API reports.
+#[derive(Debug, Clone)]
+pub struct Rejection {
+    pub status: u16,
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl Rejection {
+    pub fn new(status: u16, code: &'static str, message: impl Into<String>) -> Self {
+        Self { status, code, message: message.into() }
+    }
+
+    pub fn invalid(message: impl Into<String>) -> Self {
+        Self::new(422, "invalid_link", message)
+    }
+}
+
+/// What to do when the submitted destination already has a short link, from `DEDUP`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dedup {
+    /// Always create a new link.
+    Off,
+    /// Create a new link, but report the existing one.
+    Warn,
+    /// Hand back the existing link instead of creating one, for plain requests.
+    Reuse,
+}
+
+impl Dedup {
+    pub fn from_env(env: &Env) -> Self {
+        match config::var(env, "DEDUP").as_deref() {
+            Some("warn") => Self::Warn,
+            Some("reuse") => Self::Reuse,
+            _ => Self::Off,
+        }
+    }
+}
+
+/// Deployment settings that [`create`] follows, resolved from `[vars]` once per request.
+pub struct CreateConfig {
+    pub dedup: Dedup,
+    /// `DEDUP_SCOPE = "global"`: anyone's link counts as a duplicate, not only the creator's.
+    pub dedup_global: bool,
+    /// Status of links created without one, from [`config::redirect_status`].
+    pub redirect_status: u16,
+    /// From [`config::delete_retention_ms`]; deleted links keep their name this long.
+    pub delete_retention_ms: u64,
+    pub reserved: ReservedNames,
+    pub ids: IdStrategy,
+}
+
+impl CreateConfig {
+    pub fn from_env(env: &Env) -> Result<Self> {
+        Ok(Self {
+            dedup: Dedup::from_env(env),
+            dedup_global: config::var(env, "DEDUP_SCOPE").as_deref() == Some("global"),
+            redirect_status: config::redirect_status(env),
+            delete_retention_ms: config::delete_retention_ms(env),
+            reserved: ReservedNames::from_env(env),
+            ids: IdStrategy::from_env(env)?,
+        })
+    }
+}
+
+/// The outcome of a successful [`create`].
+#[derive(Debug, Clone)]
+pub struct Created {
+    pub id: String,
+    pub record: LinkRecord,
+    /// An existing link with the same destination was returned instead of creating one.
+    pub reused: bool,
+    /// With `DEDUP = "warn"`, an existing link that already points at the same destination.
+    pub duplicate_of: Option<String>,
+}
+
+/// Newest active link with this destination, limited to `owner`'s unless `DEDUP_SCOPE = "global"`.
+/// With `plain_only`, only links that are [plain](LinkRecord::is_plain) qualify.
+async fn find_duplicate(store: &dyn LinkStore, config: &CreateConfig, destination: &str, owner: &str, now: u64, plain_only: bool) -> Result<Option<(String, LinkRecord)>> {
+    let scope = if config.dedup_global { None } else { Some(owner) };
+    Ok(store.find_by_destination(destination, scope).await?
+        .into_iter()
+        .filter(|(_, record)| record.destination == destination && !record.is_removed() && !record.is_expired(now))
+        .filter(|(_, record)| scope.is_none_or(|o| record.owner.eq_ignore_ascii_case(o)))
+        .find(|(_, record)| !plain_only || record.is_plain(config.redirect_status)))
+}
+
+/// Lowercase the name, turn spaces into hyphens and drop anything else outside `[a-z0-9-]`.
+pub fn normalize_name(name: &str) -> Option<String> {
+    let normalized: String = name.trim()
+        .to_lowercase()
+        .replace(' ', "-")
+        .chars()
+        .filter(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || *c == '-')
+        .collect();
+    if normalized.is_empty() { None } else { Some(normalized) }
+}
+
+/// Validate `input` and store it as a new link owned by `user`, or return the existing link for
+/// the same destination when `DEDUP = "reuse"`.
+///
+/// The `allocator` hands out counter IDs and resets the click counts of the new link's name;
+/// without one (as in native tests) counter IDs are unavailable and no counts are reset.
+pub async fn create(
+    store: &dyn LinkStore,
+    allocator: Option<&Allocator>,
+    config: &CreateConfig,
+    user: &User,
+    input: NewLink,
+    now: u64,
+) -> Result<std::result::Result<Created, Rejection>> {
+    if input.url.trim().is_empty() {
+        return Ok(Err(Rejection::invalid("URL is required")));
+    }
+    let url = match destination::normalize(&input.url) {
+        Ok(url) => url,
+        Err(message) => return Ok(Err(Rejection::invalid(message))),
+    };
+
+    let expires_at = match input.expires.as_deref().map(str::trim).filter(|v| !v.is_empty()) {
+        Some(value) => match expiry::parse(value, now) {
+            Ok(at) => Some(at),
+            Err(message) => return Ok(Err(Rejection::invalid(message))),
+        },
+        None => None,
+    };
+
+    if input.status.is_some_and(|status| !REDIRECT_STATUSES.contains(&status)) {
+        return Ok(Err(Rejection::invalid("Redirect status must be 301, 302, 307 or 308")));
+    }
+
+    let geo = match geo::validate(input.geo) {
+        Ok(geo) => geo,
+        Err(message) => return Ok(Err(Rejection::invalid(message))),
+    };
+
+    let devices = match device::validate(input.devices) {
+        Ok(devices) => devices,
+        Err(message) => return Ok(Err(Rejection::invalid(message))),
+    };
+
+    let variants = match split::validate(input.variants) {
+        Ok(variants) => variants,
+        Err(message) => return Ok(Err(Rejection::invalid(message))),
+    };
+
+    if input.max_clicks == Some(0) {
+        return Ok(Err(Rejection::invalid("Click limit must be a positive whole number")));
+    }
+
+    let password = match input.password.as_deref().filter(|p| !p.is_empty()) {
+        Some(value) => Some(PasswordHash::new(value)?),
+        None => None,
+    };
+
+    let allow_reserved = input.allow_reserved && user.can(Permission::UseReservedNames);
+
+    let name = input.name.as_deref().and_then(normalize_name);
+
+    let mut record = LinkRecord::new(url.clone(), user.email.clone(), now);
+    record.expires_at = expires_at;
+    record.max_clicks = input.max_clicks;
+    record.password = password;
+    record.status = input.status.unwrap_or(config.redirect_status);
+    record.query = input.query;
+    record.flags.prefix = input.prefix;
+    record.geo = geo;
+    record.devices = devices;
+    record.variants = variants;
+    record.flags.sticky = input.sticky;
+
+    // Only a bare URL can be satisfied by an existing link; any other option asks for a new one
+    let plain = name.is_none() && record.is_plain(config.redirect_status);
+    let duplicate = match config.dedup {
+        Dedup::Off => None,
+        Dedup::Warn => find_duplicate(store, config, &url, &user.email, now, false).await?,
+        Dedup::Reuse if plain => {
+            if let Some((id, record)) = find_duplicate(store, config, &url, &user.email, now, true).await? {
+                return Ok(Ok(Created { id, record, reused: true, duplicate_of: None }));
+            }
+            None
+        },
+        Dedup::Reuse => None,
+    };
+
+    // Determine short ID: use custom name if provided and valid, otherwise generate
+    let short_id = if let Some(name) = name {
+        if name.len() < 2 || name.len() > 20 {
+            return Ok(Err(Rejection::invalid("Custom name must be 2-20 characters")));
+        }
+
+        // Route names can never be overridden since the router would shadow them
+        match config.reserved.check(&name) {
+            Some(Reservation::DenyList) if allow_reserved => {},
+            Some(reservation) => return Ok(Err(Rejection::new(422, "name_reserved", reservation.message(&name)))),
+            None => {},
+        }
+
+        if !store.put_if_absent(&name, &record).await? {
+            // Deleted links hold on to their name until the restore window has passed
+            let purgeable = match store.get(&name).await? {
+                Some(existing) => existing.deleted.is_some()
+                    && !existing.is_restorable(now, config.delete_retention_ms),
+                None => false,
+            };
+            if !(purgeable && store.delete(&name).await? && store.put_if_absent(&name, &record).await?) {
+                return Ok(Err(Rejection::new(409, "name_taken", format!("Name '{}' is already taken", name))));
+            }
+        }
+
+        name
+    } else {
+        // Auto-generate ID with collision detection
+        let mut allocated = None;
+        for attempt in 0..MAX_ID_ATTEMPTS {
+            let id = config.ids.generate(allocator, &url, attempt).await?;
+            if config.reserved.check(&id).is_some() {
+                continue;
+            }
+            if store.put_if_absent(&id, &record).await? {
+                allocated = Some(id);
+                break;
+            }
+        }
+        match allocated {
+            Some(id) => id,
+            None => return Ok(Err(Rejection::new(503, "id_unavailable", "Could not allocate a short ID, please try again"))),
+        }
+    };
+
+    // The name may have belonged to a purged or expired link; every backend starts it from zero
+    if let Some(allocator) = allocator {
+        allocator.reset(&short_id).await?;
+    }
+
+    Ok(Ok(Created {
+        id: short_id,
+        record,
+        reused: false,
+        duplicate_of: duplicate.map(|(id, _)| id),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Role;
+    use crate::id::LOWERCASE;
+    use crate::link::{Region, Tombstone, DEFAULT_STATUS};
+    use crate::store::memory::MemoryLinkStore;
+    use futures_executor::block_on;
+
+    const NOW: u64 = 1_700_000_000_000;
+    const RETENTION_MS: u64 = 30 * 24 * 60 * 60 * 1000;
+
+    fn config(dedup: Dedup) -> CreateConfig {
+        CreateConfig {
+            dedup,
+            dedup_global: false,
+            redirect_status: DEFAULT_STATUS,
+            delete_retention_ms: RETENTION_MS,
+            reserved: ReservedNames::new(vec!["acme*".to_string()]),
+            ids: IdStrategy::Random { length: 6, alphabet: LOWERCASE.as_bytes().to_vec() },
+        }
+    }
+
+    fn user(role: Role) -> User {
+        User { email: "ann@example.com".into(), groups: Vec::new(), role, scopes: None }
+    }
+
+    fn link(url: &str, name: Option<&str>) -> NewLink {
+        NewLink { url: url.into(), name: name.map(Into::into), ..NewLink::default() }
+    }
+
+    fn run(store: &MemoryLinkStore, config: &CreateConfig, role: Role, input: NewLink) -> std::result::Result<Created, Rejection> {
+        block_on(create(store, None, config, &user(role), input, NOW)).unwrap()
+    }
+
+    #[test]
+    fn custom_names_are_taken_once() {
+        let store = MemoryLinkStore::new();
+        let config = config(Dedup::Off);
+        let created = run(&store, &config, Role::Creator, link("https://a.example", Some("Docs Page"))).unwrap();
+        assert_eq!(created.id, "docs-page");
+
+        let rejection = run(&store, &config, Role::Creator, link("https://b.example", Some("docs-page"))).unwrap_err();
+        assert_eq!((rejection.status, rejection.code), (409, "name_taken"));
+        assert_eq!(block_on(store.get("docs-page")).unwrap().unwrap().destination, "https://a.example");
+    }
+
+    #[test]
+    fn reserved_names_are_rejected() {
+        let store = MemoryLinkStore::new();
+        let config = config(Dedup::Off);
+        let rejection = run(&store, &config, Role::Admin, NewLink { allow_reserved: true, ..link("https://a.example", Some("links")) }).unwrap_err();
+        assert_eq!((rejection.status, rejection.code), (422, "name_reserved"));
+
+        let rejection = run(&store, &config, Role::Creator, NewLink { allow_reserved: true, ..link("https://a.example", Some("acme-sale")) }).unwrap_err();
+        assert_eq!(rejection.code, "name_reserved");
+        let created = run(&store, &config, Role::Admin, NewLink { allow_reserved: true, ..link("https://a.example", Some("acme-sale")) }).unwrap();
+        assert_eq!(created.id, "acme-sale");
+    }
+
+    #[test]
+    fn deleted_names_are_freed_after_the_restore_window() {
+        let store = MemoryLinkStore::new();
+        let mut deleted = LinkRecord::new("https://old.example".into(), "bob@example.com".into(), 0);
+        deleted.deleted = Some(Tombstone { deleted_by: "bob@example.com".into(), deleted_at: NOW - RETENTION_MS + 1 });
+        block_on(store.put_if_absent("docs", &deleted)).unwrap();
+
+        let rejection = run(&store, &config(Dedup::Off), Role::Creator, link("https://new.example", Some("docs"))).unwrap_err();
+        assert_eq!(rejection.code, "name_taken");
+
+        deleted.deleted = Some(Tombstone { deleted_by: "bob@example.com".into(), deleted_at: NOW - RETENTION_MS });
+        block_on(store.update("docs", &deleted)).unwrap();
+        run(&store, &config(Dedup::Off), Role::Creator, link("https://new.example", Some("docs"))).unwrap();
+        let record = block_on(store.get("docs")).unwrap().unwrap();
+        assert_eq!((record.destination.as_str(), record.owner.as_str()), ("https://new.example", "ann@example.com"));
+    }
+
+    #[test]
+    fn dedup_reuse_returns_plain_links_for_plain_requests() {
+        let store = MemoryLinkStore::new();
+        let config = config(Dedup::Reuse);
+        let first = run(&store, &config, Role::Creator, link("https://a.example", None)).unwrap();
+        assert!(!first.reused);
+
+        let again = run(&store, &config, Role::Creator, link("a.example", None)).unwrap();
+        assert!(again.reused);
+        assert_eq!(again.id, first.id);
+
+        // Options ask for a new link, and links with options are never handed out
+        let prefixed = run(&store, &config, Role::Creator, NewLink { prefix: true, ..link("https://b.example", None) }).unwrap();
+        assert!(!prefixed.reused);
+        let bare = run(&store, &config, Role::Creator, link("https://b.example", None)).unwrap();
+        assert!(!bare.reused);
+        assert_ne!(bare.id, prefixed.id);
+
+        let geo = vec![GeoRule { region: Region::Country("DE".into()), destination: "https://a.example/de".into() }];
+        let with_rules = run(&store, &config, Role::Creator, NewLink { geo, ..link("https://a.example", None) }).unwrap();
+        assert!(!with_rules.reused);
+        assert_ne!(with_rules.id, first.id);
+    }
+
+    #[test]
+    fn dedup_warn_reports_the_existing_link() {
+        let store = MemoryLinkStore::new();
+        let config = config(Dedup::Warn);
+        let first = run(&store, &config, Role::Creator, link("https://a.example", None)).unwrap();
+        assert_eq!(first.duplicate_of, None);
+
+        let second = run(&store, &config, Role::Creator, link("https://a.example", Some("other"))).unwrap();
+        assert!(!second.reused);
+        assert_eq!(second.id, "other");
+        assert_eq!(second.duplicate_of, Some(first.id));
+    }
+}
@@ -0,0 +1,124 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use worker::Result;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// PBKDF2 rounds for new password hashes. Kept modest because hashing runs inside the
+/// request's CPU budget; stored alongside each hash so it can be raised later.
+const PBKDF2_ROUNDS: u32 = 25_000;
+
+/// Salted PBKDF2-HMAC-SHA256 password hash stored with a link.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PasswordHash {
+    pub salt: String,
+    pub hash: String,
+    pub rounds: u32,
+}
+
+impl PasswordHash {
+    pub fn new(password: &str) -> Result<Self> {
+        let salt = random_bytes::<16>()?;
+        Ok(Self {
+            salt: encode(&salt),
+            hash: encode(&derive(password, &salt, PBKDF2_ROUNDS)),
+            rounds: PBKDF2_ROUNDS,
+        })
+    }
+
+    pub fn verify(&self, password: &str) -> bool {
+        match (decode(&self.salt), decode(&self.hash)) {
+            (Some(salt), Some(expected)) => constant_time_eq(&derive(password, &salt, self.rounds), &expected),
+            _ => false,
+        }
+    }
+}
+
+fn derive(password: &str, salt: &[u8], rounds: u32) -> [u8; 32] {
+    pbkdf2::pbkdf2_hmac_array::<Sha256, 32>(password.as_bytes(), salt, rounds)
+}
+
+pub fn random_bytes<const N: usize>() -> Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    getrandom::getrandom(&mut buf).map_err(|e| format!("Random source unavailable: {}", e))?;
+    Ok(buf)
+}
+
+/// HMAC-SHA256 of `message`, base64url encoded.
+pub fn sign(secret: &str, message: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(message.as_bytes());
+    encode(&mac.finalize().into_bytes())
+}
+
+pub fn verify_signature(secret: &str, message: &str, signature: &str) -> bool {
+    constant_time_eq(sign(secret, message).as_bytes(), signature.as_bytes())
+}
+
+/// SHA-256 of `data`, base64url encoded. Not for passwords: use [`PasswordHash`] for those.
+pub fn digest(data: impl AsRef<[u8]>) -> String {
+    encode(&Sha256::digest(data.as_ref()))
+}
+
+pub fn verify_digest(data: &str, expected: &str) -> bool {
+    constant_time_eq(digest(data).as_bytes(), expected.as_bytes())
+}
+
+pub fn encode(bytes: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub fn decode(s: &str) -> Option<Vec<u8>> {
+    URL_SAFE_NO_PAD.decode(s).ok()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn password_hash_round_trips() {
+        let hash = PasswordHash::new("correct horse").unwrap();
+        assert_eq!(hash.rounds, PBKDF2_ROUNDS);
+        assert!(hash.verify("correct horse"));
+        assert!(!hash.verify("correct horse "));
+        assert!(!hash.verify(""));
+        assert_ne!(PasswordHash::new("correct horse").unwrap().salt, hash.salt);
+    }
+
+    #[test]
+    fn password_hash_rejects_tampering() {
+        let hash = PasswordHash::new("secret").unwrap();
+        let other = PasswordHash::new("secret").unwrap();
+        assert!(!PasswordHash { salt: other.salt.clone(), ..hash.clone() }.verify("secret"));
+        assert!(!PasswordHash { hash: other.hash.clone(), ..hash.clone() }.verify("secret"));
+        assert!(!PasswordHash { rounds: hash.rounds + 1, ..hash.clone() }.verify("secret"));
+        assert!(!PasswordHash { hash: "not base64!".into(), ..hash.clone() }.verify("secret"));
+        assert!(!PasswordHash { hash: encode(b"short"), ..hash }.verify("secret"));
+    }
+
+    #[test]
+    fn signatures_round_trip_and_detect_tampering() {
+        let signature = sign("key", "docs|1700000000|salt");
+        assert!(verify_signature("key", "docs|1700000000|salt", &signature));
+        assert!(!verify_signature("other key", "docs|1700000000|salt", &signature));
+        assert!(!verify_signature("key", "docs|1700000001|salt", &signature));
+        assert!(!verify_signature("key", "docs|1700000000|salt", &signature[1..]));
+        assert!(!verify_signature("key", "docs|1700000000|salt", ""));
+    }
+
+    #[test]
+    fn digests_are_stable_and_verifiable() {
+        assert_eq!(digest("abc"), "ungWv48Bz-pBQUDeXa4iI7ADYaOWF3qctBD_YfIAFa0");
+        assert!(verify_digest("abc", &digest("abc")));
+        assert!(!verify_digest("abd", &digest("abc")));
+        assert_eq!(decode(&encode(b"\x00\xffbytes")), Some(b"\x00\xffbytes".to_vec()));
+    }
+}
@@ -0,0 +1,33 @@
+use worker::Url;
+
+use crate::template;
+
+/// Normalize and validate a user-supplied destination URL.
+///
+/// Prepends `https://` when no protocol is given and requires a host with a domain extension.
+/// Template placeholders such as `{1}` are checked with [`template::validate`].
+pub fn normalize(input: &str) -> Result<String, &'static str> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err("URL is required");
+    }
+
+    // Auto-prepend https:// if no protocol specified
+    let url = if !input.starts_with("http://") && !input.starts_with("https://") {
+        format!("https://{}", input)
+    } else {
+        input.to_string()
+    };
+
+    template::validate(&url)?;
+
+    // Validate URL format and domain structure
+    let parsed_url = Url::parse(&url).map_err(|_| "Invalid URL format")?;
+
+    // Ensure URL has a valid host with at least a domain extension
+    match parsed_url.host_str() {
+        Some(host) if host.contains('.') => Ok(url),
+        Some(_) => Err("URL must include a domain extension (e.g., .com, .org)"),
+        None => Err("URL must include a valid domain"),
+    }
+}
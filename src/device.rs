@@ -0,0 +1,165 @@
+use crate::destination;
+use crate::link::{DeviceRule, Platform};
+
+/// Substrings of a lowercased User-Agent that mark crawlers, link preview fetchers and scripts.
+/// Matched before anything else, since many of them also claim to be Android or a Mac.
+const BOT_MARKERS: &[&str] = &[
+    "bot/", "+http", "googlebot", "bingbot", "slackbot", "twitterbot", "linkedinbot", "discordbot",
+    "telegrambot", "yandexbot", "duckduckbot", "applebot", "ahrefsbot", "semrushbot", "petalbot",
+    "facebot", "facebookexternalhit", "whatsapp/", "baiduspider", "slurp", "crawler", "spider",
+    "headlesschrome", "lighthouse", "curl/", "wget/", "python-requests", "python-urllib",
+    "go-http-client", "java/",
+];
+
+/// Clients that mention Android or a desktop OS but are neither, e.g. Windows Phone claims to be
+/// both Android and an iPhone, and Tizen TVs report Linux.
+const OTHER_MARKERS: &[&str] = &["windows phone", "kaios", "smart-tv", "smarttv", "tizen", "webos", "web0s"];
+
+const DESKTOP_MARKERS: &[&str] = &["windows nt", "macintosh", "x11", "cros", "linux"];
+
+/// Tell what kind of client sent `user_agent`. `None` means it fits none of the [`Platform`]s,
+/// such as a feature phone or a TV, and gets the link's default destination.
+///
+/// iPads on iPadOS 13 and later ask for desktop sites and send a Mac User-Agent, so they are
+/// classified as desktop.
+pub fn classify(user_agent: &str) -> Option<Platform> {
+    let ua = user_agent.trim().to_ascii_lowercase();
+    let has = |markers: &[&str]| markers.iter().any(|marker| ua.contains(marker));
+
+    if ua.is_empty() || has(BOT_MARKERS) {
+        Some(Platform::Bot)
+    } else if has(OTHER_MARKERS) {
+        None
+    } else if has(&["iphone", "ipad", "ipod"]) {
+        Some(Platform::Ios)
+    } else if ua.contains("android") {
+        Some(Platform::Android)
+    } else if ua.contains("mobile") {
+        None
+    } else if has(DESKTOP_MARKERS) {
+        Some(Platform::Desktop)
+    } else {
+        None
+    }
+}
+
+/// Normalize destinations, dropping rules with an empty one and rejecting duplicate platforms.
+pub fn validate(rules: Vec<DeviceRule>) -> Result<Vec<DeviceRule>, String> {
+    let mut validated: Vec<DeviceRule> = Vec::with_capacity(rules.len());
+    for rule in rules.into_iter().filter(|rule| !rule.destination.trim().is_empty()) {
+        if validated.iter().any(|r| r.platform == rule.platform) {
+            return Err(format!("There is more than one rule for {}", rule.platform.label()));
+        }
+        let destination = destination::normalize(&rule.destination)
+            .map_err(|message| format!("{}: {}", rule.platform.label(), message))?;
+        validated.push(DeviceRule { platform: rule.platform, destination });
+    }
+    Ok(validated)
+}
+
+/// The destination for visitors on `platform`, if the link has a rule for it.
+pub fn select(rules: &[DeviceRule], platform: Option<Platform>) -> Option<&str> {
+    let platform = platform?;
+    rules.iter().find(|rule| rule.platform == platform).map(|rule| rule.destination.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// User-Agents seen in the wild, with the platform each should be sent to.
+    const FIXTURES: &[(&str, Option<Platform>)] = &[
+        // iOS: Safari, other browsers and in-app browsers
+        ("Mozilla/5.0 (iPhone; CPU iPhone OS 17_5 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.5 Mobile/15E148 Safari/604.1", Some(Platform::Ios)),
+        ("Mozilla/5.0 (iPhone; CPU iPhone OS 17_5 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) CriOS/126.0.6478.54 Mobile/15E148 Safari/604.1", Some(Platform::Ios)),
+        ("Mozilla/5.0 (iPhone; CPU iPhone OS 17_5 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) FxiOS/127.0 Mobile/15E148 Safari/605.1.15", Some(Platform::Ios)),
+        ("Mozilla/5.0 (iPad; CPU OS 16_6 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/16.6 Mobile/15E148 Safari/604.1", Some(Platform::Ios)),
+        ("Mozilla/5.0 (iPod touch; CPU iPhone OS 12_5_7 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/12.1.2 Mobile/15E148 Safari/604.1", Some(Platform::Ios)),
+        ("Mozilla/5.0 (iPhone; CPU iPhone OS 16_6 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Mobile/15E148 Instagram 295.0.0.32.109 (iPhone14,5; iOS 16_6; en_US; en; scale=3.00; 1170x2532; 500160598)", Some(Platform::Ios)),
+        ("Mozilla/5.0 (iPhone; CPU iPhone OS 17_4_1 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Mobile/15E148 [FBAN/FBIOS;FBAV/458.0.0.38.107;FBBV/573411335;FBDV/iPhone15,3;FBMD/iPhone;FBSN/iOS;FBSV/17.4.1;FBSS/3;FBID/phone;FBLC/en_US;FBOP/5;FBRV/575022396]", Some(Platform::Ios)),
+        // Android: phones, tablets, WebViews and vendor browsers
+        ("Mozilla/5.0 (Linux; Android 10; K) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Mobile Safari/537.36", Some(Platform::Android)),
+        ("Mozilla/5.0 (Linux; Android 14; SAMSUNG SM-S918B) AppleWebKit/537.36 (KHTML, like Gecko) SamsungBrowser/25.0 Chrome/121.0.0.0 Mobile Safari/537.36", Some(Platform::Android)),
+        ("Mozilla/5.0 (Android 14; Mobile; rv:127.0) Gecko/127.0 Firefox/127.0", Some(Platform::Android)),
+        ("Mozilla/5.0 (Linux; Android 13; SM-X700) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36", Some(Platform::Android)),
+        ("Mozilla/5.0 (Linux; Android 13; Pixel 7 Build/TQ3A.230805.001; wv) AppleWebKit/537.36 (KHTML, like Gecko) Version/4.0 Chrome/116.0.5845.163 Mobile Safari/537.36 Instagram 298.0.0.31.110 Android (33/13; 420dpi; 1080x2400; Google/google; Pixel 7; panther; panther; en_US; 511046234)", Some(Platform::Android)),
+        ("Mozilla/5.0 (Linux; Android 12; HarmonyOS; NOH-NX9; HMSCore 6.13.0.302) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/99.0.4844.88 HuaweiBrowser/14.0.5.302 Mobile Safari/537.36", Some(Platform::Android)),
+        // A phone brand with "bot" in its name
+        ("Mozilla/5.0 (Linux; Android 10; CUBOT X30) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/112.0.0.0 Mobile Safari/537.36", Some(Platform::Android)),
+        // Desktop
+        ("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36", Some(Platform::Desktop)),
+        ("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36 Edg/126.0.0.0", Some(Platform::Desktop)),
+        ("Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:127.0) Gecko/20100101 Firefox/127.0", Some(Platform::Desktop)),
+        ("Mozilla/5.0 (Windows NT 10.0; WOW64; Trident/7.0; rv:11.0) like Gecko", Some(Platform::Desktop)),
+        ("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.5 Safari/605.1.15", Some(Platform::Desktop)),
+        ("Mozilla/5.0 (X11; Ubuntu; Linux x86_64; rv:127.0) Gecko/20100101 Firefox/127.0", Some(Platform::Desktop)),
+        ("Mozilla/5.0 (X11; CrOS x86_64 14541.0.0) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36", Some(Platform::Desktop)),
+        // Crawlers and link previews, including ones posing as Android or a Mac
+        ("Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)", Some(Platform::Bot)),
+        ("Mozilla/5.0 (Linux; Android 6.0.1; Nexus 5X Build/MMB29P) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.6478.126 Mobile Safari/537.36 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)", Some(Platform::Bot)),
+        ("Mozilla/5.0 (compatible; bingbot/2.0; +http://www.bing.com/bingbot.htm)", Some(Platform::Bot)),
+        ("Mozilla/5.0 (compatible; YandexBot/3.0; +http://yandex.com/bots)", Some(Platform::Bot)),
+        ("Mozilla/5.0 (compatible; Baiduspider/2.0; +http://www.baidu.com/search/spider.html)", Some(Platform::Bot)),
+        ("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_5) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/13.1.1 Safari/605.1.15 (Applebot/0.1; +http://www.apple.com/go/applebot)", Some(Platform::Bot)),
+        ("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_11_1) AppleWebKit/601.2.4 (KHTML, like Gecko) Version/9.0.1 Safari/601.2.4 facebookexternalhit/1.1 Facebot Twitterbot/1.0", Some(Platform::Bot)),
+        ("facebookexternalhit/1.1 (+http://www.facebook.com/externalhit_uatext.php)", Some(Platform::Bot)),
+        ("Slackbot-LinkExpanding 1.0 (+https://api.slack.com/robots)", Some(Platform::Bot)),
+        ("Twitterbot/1.0", Some(Platform::Bot)),
+        ("LinkedInBot/1.0 (compatible; Mozilla/5.0; Apache-HttpClient +http://www.linkedin.com)", Some(Platform::Bot)),
+        ("Mozilla/5.0 (compatible; Discordbot/2.0; +https://discordapp.com)", Some(Platform::Bot)),
+        ("TelegramBot (like TwitterBot)", Some(Platform::Bot)),
+        ("WhatsApp/2.23.20.0 A", Some(Platform::Bot)),
+        ("Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) HeadlessChrome/126.0.0.0 Safari/537.36", Some(Platform::Bot)),
+        ("curl/8.4.0", Some(Platform::Bot)),
+        ("Wget/1.21.4", Some(Platform::Bot)),
+        ("python-requests/2.31.0", Some(Platform::Bot)),
+        ("Go-http-client/2.0", Some(Platform::Bot)),
+        ("", Some(Platform::Bot)),
+        // Neither phone platform nor desktop
+        ("Mozilla/5.0 (Mobile; Windows Phone 8.1; Android 4.0; ARM; Trident/7.0; Touch; rv:11.0; IEMobile/11.0; NOKIA; Lumia 635) like iPhone OS 7_0_3 Mac OS X AppleWebKit/537 (KHTML, like Gecko) Mobile Safari/537", None),
+        ("Mozilla/5.0 (Mobile; LYF/F300B/LYF-F300B-001-01-15-130718-i;Android; rv:48.0) Gecko/48.0 Firefox/48.0 KAIOS/2.5", None),
+        ("Mozilla/5.0 (BB10; Touch) AppleWebKit/537.35+ (KHTML, like Gecko) Version/10.3.3.2205 Mobile Safari/537.35+", None),
+        ("Mozilla/5.0 (SMART-TV; LINUX; Tizen 6.0) AppleWebKit/537.36 (KHTML, like Gecko) 76.0.3809.146/6.0 TV Safari/537.36", None),
+        ("Mozilla/5.0 (Web0S; Linux/SmartTV) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/79.0.3945.79 Safari/537.36 WebAppManager", None),
+    ];
+
+    #[test]
+    fn classifies_real_world_user_agents() {
+        let failures: Vec<String> = FIXTURES.iter()
+            .filter(|(ua, expected)| classify(ua) != *expected)
+            .map(|(ua, expected)| format!("{:?} => {:?}, expected {:?}", ua, classify(ua), expected))
+            .collect();
+        assert!(failures.is_empty(), "misclassified:\n{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn fixtures_cover_every_platform() {
+        for platform in Platform::ALL {
+            assert!(FIXTURES.iter().any(|(_, expected)| *expected == Some(platform)), "no fixture for {:?}", platform);
+        }
+    }
+
+    #[test]
+    fn validate_normalizes_and_drops_empty_rules() {
+        let rules = validate(vec![
+            DeviceRule { platform: Platform::Ios, destination: "apps.apple.com/app/id123".into() },
+            DeviceRule { platform: Platform::Android, destination: " ".into() },
+        ]).unwrap();
+        assert_eq!(rules, vec![DeviceRule { platform: Platform::Ios, destination: "https://apps.apple.com/app/id123".into() }]);
+    }
+
+    #[test]
+    fn validate_rejects_duplicates_and_bad_urls() {
+        let rule = |destination: &str| DeviceRule { platform: Platform::Android, destination: destination.into() };
+        assert!(validate(vec![rule("https://play.google.com"), rule("https://example.com")]).is_err());
+        assert!(validate(vec![rule("not-a-domain")]).is_err());
+    }
+
+    #[test]
+    fn select_falls_back_when_no_rule_matches() {
+        let rules = vec![DeviceRule { platform: Platform::Ios, destination: "https://apps.apple.com/app/id123".into() }];
+        assert_eq!(select(&rules, Some(Platform::Ios)), Some("https://apps.apple.com/app/id123"));
+        assert_eq!(select(&rules, Some(Platform::Android)), None);
+        assert_eq!(select(&rules, None), None);
+    }
+}
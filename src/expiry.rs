@@ -0,0 +1,124 @@
+use crate::time;
+
+/// Furthest ahead an expiry may be set, about ten years.
+const MAX_HORIZON_MS: u64 = 10 * 365 * 24 * 60 * 60 * 1000;
+/// Years beyond this are refused before any date arithmetic is done.
+const MAX_YEAR: i64 = 9999;
+
+/// Parse the optional expiry supplied at creation into an absolute epoch-millis timestamp.
+///
+/// Accepts a duration relative to `now` (`30m`, `12h`, `7d`, `2w`) or an absolute UTC date
+/// (`2025-12-31`, `2025-12-31T18:00`, `2025-12-31T18:00:00Z`) as produced by `datetime-local` inputs,
+/// no further ahead than [`MAX_HORIZON_MS`].
+pub fn parse(input: &str, now: u64) -> Result<u64, String> {
+    let input = input.trim();
+    let expires_at = match parse_duration(input) {
+        Some(secs) => secs.checked_mul(1000).and_then(|ms| now.checked_add(ms)),
+        None => Some(parse_datetime(input).ok_or_else(|| {
+            "Expiry must be a duration like 7d or 12h, or a date like 2025-12-31T18:00".to_string()
+        })?),
+    };
+
+    match expires_at {
+        Some(at) if at <= now => Err("Expiry must be in the future".to_string()),
+        Some(at) if at - now <= MAX_HORIZON_MS => Ok(at),
+        _ => Err("Expiry must be within 10 years".to_string()),
+    }
+}
+
+fn parse_duration(input: &str) -> Option<u64> {
+    let unit = input.chars().last()?;
+    let amount: u64 = input[..input.len() - unit.len_utf8()].parse().ok()?;
+    let multiplier = match unit {
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        'w' => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    amount.checked_mul(multiplier)
+}
+
+fn parse_datetime(input: &str) -> Option<u64> {
+    let input = input.strip_suffix('Z').unwrap_or(input);
+    let (date, time) = match input.split_once(['T', ' ']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (input, None),
+    };
+
+    let mut date_parts = date.splitn(3, '-').map(|p| p.parse::<i64>().ok());
+    let (year, month, day) = (date_parts.next()??, date_parts.next()??, date_parts.next()??);
+    if !(0..=MAX_YEAR).contains(&year) || !(1..=12).contains(&month) || !(1..=time::days_in_month(year, month)).contains(&day) {
+        return None;
+    }
+
+    let (hour, minute, second) = match time {
+        Some(time) => {
+            let mut parts = time.splitn(3, ':').map(|p| p.parse::<i64>().ok());
+            (parts.next()??, parts.next()??, parts.next().unwrap_or(Some(0))?)
+        },
+        None => (0, 0, 0),
+    };
+    if !(0..24).contains(&hour) || !(0..60).contains(&minute) || !(0..60).contains(&second) {
+        return None;
+    }
+
+    let secs = time::days_from_civil(year, month, day).checked_mul(86_400)?
+        .checked_add(hour * 3600 + minute * 60 + second)?;
+    u64::try_from(secs).ok()?.checked_mul(1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2025-01-01T00:00:00Z
+    const NOW: u64 = 1_735_689_600_000;
+
+    #[test]
+    fn parse_accepts_durations() {
+        assert_eq!(parse("30m", NOW), Ok(NOW + 30 * 60_000));
+        assert_eq!(parse(" 12h ", NOW), Ok(NOW + 12 * 3_600_000));
+        assert_eq!(parse("7d", NOW), Ok(NOW + 7 * 86_400_000));
+        assert_eq!(parse("2w", NOW), Ok(NOW + 14 * 86_400_000));
+        assert!(parse("0d", NOW).is_err());
+    }
+
+    #[test]
+    fn parse_accepts_utc_dates() {
+        assert_eq!(parse("2025-12-31", NOW), Ok(1_767_139_200_000));
+        assert_eq!(parse("2025-12-31T18:00", NOW), Ok(1_767_139_200_000 + 18 * 3_600_000));
+        assert_eq!(parse("2025-12-31 18:00:30Z", NOW), Ok(1_767_139_200_000 + 18 * 3_600_000 + 30_000));
+        assert_eq!(parse("2028-02-29", NOW), Ok(1_835_395_200_000));
+    }
+
+    #[test]
+    fn parse_rejects_impossible_dates() {
+        assert!(parse("2025-02-29", NOW).is_err());
+        assert!(parse("2025-02-31", NOW).is_err());
+        assert!(parse("2025-04-31", NOW).is_err());
+        assert!(parse("2100-02-29", NOW).is_err());
+        assert!(parse("2025-13-01", NOW).is_err());
+        assert!(parse("2025-12-31T24:00", NOW).is_err());
+    }
+
+    #[test]
+    fn parse_rejects_expiries_past_the_horizon() {
+        assert!(parse("3650d", NOW).is_ok());
+        assert!(parse("3651d", NOW).is_err());
+        assert!(parse("2040-01-01", NOW).is_err());
+        assert!(parse("1000000000-01-01", NOW).is_err());
+        assert!(parse("9223372036854775807-01-01", NOW).is_err());
+        assert!(parse("18446744073709551615m", NOW).is_err());
+        assert!(parse("30000000000000000w", NOW).is_err());
+    }
+
+    #[test]
+    fn parse_rejects_past_and_malformed_input() {
+        assert!(parse("2024-12-31", NOW).is_err());
+        assert!(parse("2025-01-01", NOW).is_err());
+        for input in ["", "soon", "7", "7y", "-1d", "2025-12", "2025-12-31T18", "31/12/2025"] {
+            assert!(parse(input, NOW).is_err(), "{}", input);
+        }
+    }
+}
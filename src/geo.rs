@@ -0,0 +1,135 @@
+use crate::destination;
+use crate::link::{GeoRule, Region};
+
+/// Continent codes Cloudflare reports in `request.cf.continent`.
+pub const CONTINENTS: &[&str] = &["AF", "AN", "AS", "EU", "NA", "OC", "SA"];
+const MAX_RULES: usize = 50;
+/// Written before a continent code in the one-rule-per-line form, since some continent codes
+/// are also country codes (`NA` is Namibia, `SA` Saudi Arabia).
+const CONTINENT_PREFIX: &str = "continent:";
+
+/// Uppercase region codes and normalize destinations, rejecting unknown codes and duplicates.
+pub fn validate(rules: Vec<GeoRule>) -> Result<Vec<GeoRule>, String> {
+    if rules.len() > MAX_RULES {
+        return Err(format!("A link can have at most {} location rules", MAX_RULES));
+    }
+
+    let mut validated: Vec<GeoRule> = Vec::with_capacity(rules.len());
+    for rule in rules {
+        let region = match rule.region {
+            Region::Country(code) => {
+                let code = code.trim().to_ascii_uppercase();
+                if code.len() != 2 || !code.bytes().all(|b| b.is_ascii_alphabetic()) {
+                    return Err(format!("'{}' is not a two-letter country code", code));
+                }
+                Region::Country(code)
+            },
+            Region::Continent(code) => {
+                let code = code.trim().to_ascii_uppercase();
+                if !CONTINENTS.contains(&code.as_str()) {
+                    return Err(format!("'{}' is not a continent code ({})", code, CONTINENTS.join(", ")));
+                }
+                Region::Continent(code)
+            },
+        };
+        if validated.iter().any(|r| r.region == region) {
+            return Err(format!("There is more than one rule for {}", label(&region)));
+        }
+        let destination = destination::normalize(&rule.destination)
+            .map_err(|message| format!("{}: {}", label(&region), message))?;
+        validated.push(GeoRule { region, destination });
+    }
+    Ok(validated)
+}
+
+/// Parse rules written one per line as `DE https://shop.example.de` or
+/// `continent:EU https://shop.example.eu`. Blank lines are ignored.
+pub fn parse(text: &str) -> Result<Vec<GeoRule>, String> {
+    let mut rules = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut parts = line.split_whitespace();
+        let (Some(code), Some(destination), None) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(format!("Location rule on line {} must be a region code and a URL", number + 1));
+        };
+        let region = match code.get(..CONTINENT_PREFIX.len()) {
+            Some(prefix) if prefix.eq_ignore_ascii_case(CONTINENT_PREFIX) => Region::Continent(code[CONTINENT_PREFIX.len()..].to_string()),
+            _ => Region::Country(code.to_string()),
+        };
+        rules.push(GeoRule { region, destination: destination.to_string() });
+    }
+    validate(rules)
+}
+
+/// The inverse of [`parse`], for pre-filling the edit form.
+pub fn format(rules: &[GeoRule]) -> String {
+    rules.iter()
+        .map(|rule| format!("{} {}", label(&rule.region), rule.destination))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn label(region: &Region) -> String {
+    match region {
+        Region::Country(code) => code.clone(),
+        Region::Continent(code) => format!("{}{}", CONTINENT_PREFIX, code),
+    }
+}
+
+/// The destination for a visitor from `country` on `continent`. A country rule beats a continent
+/// rule; `None` means no rule matched and the link's own destination applies.
+pub fn select<'a>(rules: &'a [GeoRule], country: Option<&str>, continent: Option<&str>) -> Option<&'a str> {
+    let by_country = country.and_then(|country| rules.iter()
+        .find(|rule| matches!(&rule.region, Region::Country(c) if c.eq_ignore_ascii_case(country))));
+    let by_continent = || continent.and_then(|continent| rules.iter()
+        .find(|rule| matches!(&rule.region, Region::Continent(c) if c.eq_ignore_ascii_case(continent))));
+    by_country.or_else(by_continent).map(|rule| rule.destination.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules() -> Vec<GeoRule> {
+        parse("continent:EU https://shop.example.eu\nDE https://shop.example.de\n\nUS shop.example.com").unwrap()
+    }
+
+    #[test]
+    fn parse_reads_countries_and_continents() {
+        assert_eq!(rules(), vec![
+            GeoRule { region: Region::Continent("EU".into()), destination: "https://shop.example.eu".into() },
+            GeoRule { region: Region::Country("DE".into()), destination: "https://shop.example.de".into() },
+            GeoRule { region: Region::Country("US".into()), destination: "https://shop.example.com".into() },
+        ]);
+        assert_eq!(parse("na https://example.com").unwrap()[0].region, Region::Country("NA".into()));
+        assert_eq!(parse("Continent:na https://example.com").unwrap()[0].region, Region::Continent("NA".into()));
+    }
+
+    #[test]
+    fn parse_rejects_malformed_rules() {
+        assert!(parse("DE").is_err());
+        assert!(parse("DE https://example.de extra").is_err());
+        assert!(parse("DEU https://example.de").is_err());
+        assert!(parse("continent:XX https://example.com").is_err());
+        assert!(parse("DE not-a-domain").is_err());
+        assert!(parse("DE https://a.example\nde https://b.example").is_err());
+    }
+
+    #[test]
+    fn format_round_trips() {
+        assert_eq!(parse(&format(&rules())).unwrap(), rules());
+    }
+
+    #[test]
+    fn select_prefers_country_over_continent() {
+        let rules = rules();
+        assert_eq!(select(&rules, Some("DE"), Some("EU")), Some("https://shop.example.de"));
+        assert_eq!(select(&rules, Some("FR"), Some("EU")), Some("https://shop.example.eu"));
+        assert_eq!(select(&rules, Some("us"), Some("NA")), Some("https://shop.example.com"));
+        assert_eq!(select(&rules, Some("JP"), Some("AS")), None);
+        assert_eq!(select(&rules, None, None), None);
+    }
+}
@@ -0,0 +1,176 @@
+use sha2::{Digest, Sha256};
+use worker::{Env, Result};
+
+use crate::allocator::Allocator;
+use crate::config;
+use crate::crypto;
+
+pub const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz0123456789";
+/// Lowercase alphanumerics without the easily confused `0`, `o`, `1`, `l` and `i`.
+pub const UNAMBIGUOUS: &str = "abcdefghjkmnpqrstuvwxyz23456789";
+pub const BASE62: &str = "0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+pub const DEFAULT_LENGTH: usize = 6;
+
+/// How auto-generated short IDs are produced, selected with the `ID_STRATEGY` var.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IdStrategy {
+    /// Cryptographically random characters from `alphabet` (`ID_STRATEGY = "random"`, the default).
+    Random { length: usize, alphabet: Vec<u8> },
+    /// Base62 encoding of a strongly consistent counter (`ID_STRATEGY = "counter"`).
+    Counter,
+    /// Characters derived from a SHA-256 of the destination (`ID_STRATEGY = "hash"`).
+    Hashed { length: usize, alphabet: Vec<u8> },
+}
+
+impl IdStrategy {
+    /// Build the strategy from `ID_STRATEGY`, `ID_LENGTH` and `ID_ALPHABET`.
+    ///
+    /// `ID_ALPHABET` accepts `lowercase` (default), `unambiguous`, `base62`, or a literal set of characters.
+    pub fn from_env(env: &Env) -> Result<Self> {
+        let length = config::var_or(env, "ID_LENGTH", DEFAULT_LENGTH);
+        if !(2..=20).contains(&length) {
+            return Err("ID_LENGTH must be between 2 and 20".into());
+        }
+        let alphabet = parse_alphabet(config::var(env, "ID_ALPHABET").as_deref())?;
+
+        match config::var(env, "ID_STRATEGY").as_deref() {
+            None | Some("random") => Ok(Self::Random { length, alphabet }),
+            Some("counter") => Ok(Self::Counter),
+            Some("hash") => Ok(Self::Hashed { length, alphabet }),
+            Some(other) => Err(format!("Unknown ID_STRATEGY '{}'", other).into()),
+        }
+    }
+
+    /// Produce a candidate ID. `attempt` counts previous collisions for this request; after the
+    /// first, hashed IDs mix in random bytes so repeated links to one URL still find a free ID.
+    /// Counter IDs need the `allocator`.
+    pub async fn generate(&self, allocator: Option<&Allocator>, destination: &str, attempt: u32) -> Result<String> {
+        match self {
+            Self::Random { length, alphabet } => random_id(*length, alphabet),
+            Self::Counter => {
+                let allocator = allocator.ok_or_else(|| worker::Error::from("Counter IDs need the ID allocator"))?;
+                Ok(encode_base62(allocator.next_counter().await?))
+            },
+            Self::Hashed { length, alphabet } => {
+                let salt = if attempt == 0 { Vec::new() } else { crypto::random_bytes::<16>()?.to_vec() };
+                Ok(hashed_id(destination, &salt, *length, alphabet))
+            },
+        }
+    }
+}
+
+fn parse_alphabet(value: Option<&str>) -> Result<Vec<u8>> {
+    let alphabet = match value {
+        None | Some("lowercase") => LOWERCASE,
+        Some("unambiguous") => UNAMBIGUOUS,
+        Some("base62") => BASE62,
+        Some(custom) => custom,
+    };
+
+    let mut bytes = alphabet.as_bytes().to_vec();
+    bytes.sort_unstable();
+    bytes.dedup();
+    if bytes.len() < 2 || !bytes.iter().all(|b| b.is_ascii_alphanumeric() || *b == b'-') {
+        return Err("ID_ALPHABET must contain at least two distinct characters from [A-Za-z0-9-]".into());
+    }
+    Ok(bytes)
+}
+
+pub fn random_id(length: usize, alphabet: &[u8]) -> Result<String> {
+    // Rejection sampling keeps every character equally likely for alphabets that don't divide 256
+    let limit = 256 - (256 % alphabet.len());
+    let mut id = String::with_capacity(length);
+    let mut buf = [0u8; 32];
+    while id.len() < length {
+        getrandom::getrandom(&mut buf).map_err(|e| format!("Random source unavailable: {}", e))?;
+        for &b in buf.iter().filter(|&&b| (b as usize) < limit) {
+            if id.len() == length {
+                break;
+            }
+            id.push(alphabet[b as usize % alphabet.len()] as char);
+        }
+    }
+    Ok(id)
+}
+
+/// Derive an ID from `destination`; with an empty `salt` the same URL always gets the same ID.
+pub fn hashed_id(destination: &str, salt: &[u8], length: usize, alphabet: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(destination.as_bytes());
+    hasher.update(salt);
+    hasher.finalize()
+        .iter()
+        .take(length)
+        .map(|b| alphabet[*b as usize % alphabet.len()] as char)
+        .collect()
+}
+
+pub fn encode_base62(mut n: u64) -> String {
+    let chars = BASE62.as_bytes();
+    let mut out = Vec::new();
+    loop {
+        out.push(chars[(n % 62) as usize]);
+        n /= 62;
+        if n == 0 {
+            break;
+        }
+    }
+    out.reverse();
+    String::from_utf8(out).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_base62(id: &str) -> u64 {
+        id.bytes().fold(0, |n, b| n * 62 + BASE62.bytes().position(|c| c == b).unwrap() as u64)
+    }
+
+    #[test]
+    fn random_id_has_length_and_alphabet() {
+        for alphabet in [LOWERCASE, UNAMBIGUOUS, BASE62, "ab"] {
+            for length in [2, 6, 20] {
+                let id = random_id(length, alphabet.as_bytes()).unwrap();
+                assert_eq!(id.len(), length);
+                assert!(id.chars().all(|c| alphabet.contains(c)), "{} not in {}", id, alphabet);
+            }
+        }
+    }
+
+    #[test]
+    fn hashed_id_is_stable_without_salt() {
+        let id = hashed_id("https://example.com", &[], 6, LOWERCASE.as_bytes());
+        assert_eq!(id, hashed_id("https://example.com", &[], 6, LOWERCASE.as_bytes()));
+        assert_ne!(id, hashed_id("https://example.org", &[], 6, LOWERCASE.as_bytes()));
+        assert_eq!(id.len(), 6);
+        assert!(id.chars().all(|c| LOWERCASE.contains(c)));
+    }
+
+    #[test]
+    fn hashed_id_with_salt_escapes_collisions() {
+        let ids: std::collections::HashSet<String> = (0..32)
+            .map(|_| hashed_id("https://example.com", &crypto::random_bytes::<16>().unwrap(), 6, LOWERCASE.as_bytes()))
+            .collect();
+        assert!(ids.len() > 30, "salted IDs should almost never repeat");
+    }
+
+    #[test]
+    fn encode_base62_round_trips() {
+        assert_eq!(encode_base62(0), "0");
+        assert_eq!(encode_base62(61), "Z");
+        assert_eq!(encode_base62(62), "10");
+        for n in [1, 62, 3843, 3844, 1_000_000, u64::MAX] {
+            assert_eq!(decode_base62(&encode_base62(n)), n);
+        }
+    }
+
+    #[test]
+    fn parse_alphabet_dedups_and_validates() {
+        assert_eq!(parse_alphabet(Some("aab")).unwrap(), b"ab".to_vec());
+        assert_eq!(parse_alphabet(None).unwrap().len(), LOWERCASE.len());
+        assert!(parse_alphabet(Some("a")).is_err());
+        assert!(parse_alphabet(Some("ab_")).is_err());
+    }
+}
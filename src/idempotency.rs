@@ -0,0 +1,120 @@
+use serde::{Deserialize, Serialize};
+use worker::{Env, Headers, Request, Response, Result};
+
+use crate::allocator::Allocator;
+use crate::creation::Rejection;
+use crate::crypto;
+
+pub const HEADER: &str = "Idempotency-Key";
+const KEY_PREFIX: &str = "idempotency:";
+const MAX_KEY_LENGTH: usize = 255;
+/// How long the first response to a key is replayed for.
+const RESULT_TTL_SECS: u64 = 24 * 60 * 60;
+/// How long a key counts as in flight when its request never finishes.
+const PENDING_TTL_SECS: u64 = 60;
+
+/// What was recorded for a key: a marker while the first request runs, then its response.
+/// Held by an [`Allocator`] object named after the key, so claiming a key is atomic.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    fingerprint: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    response: Option<StoredResponse>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+pub enum Check {
+    /// No key was sent; handle the request normally.
+    Untracked,
+    /// First use of the key; handle the request and pass the outcome to [`Pending::finish`].
+    Pending(Pending),
+    /// Retry of a finished request: send this stored response unchanged.
+    Replay(Response),
+    /// The key is malformed, still in flight, or was used with a different body.
+    Rejected(Rejection),
+}
+
+pub struct Pending {
+    allocator: Allocator,
+    key: String,
+    fingerprint: String,
+}
+
+/// Look up the `Idempotency-Key` of `req`, scoped to `owner` and the request path so keys from
+/// different users or endpoints never collide. Reads the body from a clone, leaving `req` intact.
+pub async fn check(env: &Env, req: &Request, owner: &str) -> Result<Check> {
+    let Some(key) = req.headers().get(HEADER)? else {
+        return Ok(Check::Untracked);
+    };
+    let key = key.trim();
+    if key.is_empty() || key.len() > MAX_KEY_LENGTH {
+        return Ok(Check::Rejected(Rejection::new(400, "invalid_idempotency_key", format!("{} must be 1-{} characters", HEADER, MAX_KEY_LENGTH))));
+    }
+
+    let path = req.path();
+    let content_type = req.headers().get("content-type")?.unwrap_or_default();
+    let mut request = content_type.into_bytes();
+    request.push(b'\n');
+    request.extend(req.clone()?.bytes().await?);
+    let fingerprint = crypto::digest(request);
+
+    // The prefix keeps these objects apart from short IDs, which never contain `:`
+    let allocator = Allocator::from_env(env)?;
+    let key = format!("{}{}", KEY_PREFIX, crypto::digest(format!("{}\n{}\n{}", owner.to_lowercase(), path, key)));
+    let pending = serde_json::to_string(&Entry { fingerprint: fingerprint.clone(), response: None })?;
+    let existing = match allocator.claim(&key, &pending, PENDING_TTL_SECS).await? {
+        Some(value) => Some(serde_json::from_str::<Entry>(&value)?),
+        None => None,
+    };
+    Ok(match existing {
+        Some(entry) if entry.fingerprint != fingerprint => Check::Rejected(Rejection::new(
+            422, "idempotency_key_reused", format!("This {} was already used with a different request body", HEADER),
+        )),
+        Some(Entry { response: Some(stored), .. }) => Check::Replay(replay(&stored)?),
+        Some(_) => Check::Rejected(Rejection::new(
+            409, "idempotency_key_in_use", format!("A request with this {} is still being processed", HEADER),
+        )),
+        None => Check::Pending(Pending { allocator, key, fingerprint }),
+    })
+}
+
+impl Pending {
+    /// Record `response` for replay and return it. Errors and server errors are not recorded
+    /// and free the key, so a retry after a transient failure runs again.
+    pub async fn finish(self, response: Result<Response>) -> Result<Response> {
+        let response = match response {
+            Ok(response) if response.status_code() < 500 => self.record(response).await,
+            other => other,
+        };
+        if !matches!(&response, Ok(r) if r.status_code() < 500) {
+            self.allocator.unclaim(&self.key).await?;
+        }
+        response
+    }
+
+    async fn record(&self, mut response: Response) -> Result<Response> {
+        let stored = StoredResponse {
+            status: response.status_code(),
+            headers: response.headers().entries().collect(),
+            body: response.text().await?,
+        };
+        let response = replay(&stored)?;
+        let entry = Entry { fingerprint: self.fingerprint.clone(), response: Some(stored) };
+        self.allocator.settle(&self.key, &serde_json::to_string(&entry)?, RESULT_TTL_SECS).await?;
+        Ok(response)
+    }
+}
+
+fn replay(stored: &StoredResponse) -> Result<Response> {
+    let headers = Headers::new();
+    for (name, value) in &stored.headers {
+        headers.append(name, value)?;
+    }
+    Ok(Response::ok(stored.body.clone())?.with_status(stored.status).with_headers(headers))
+}
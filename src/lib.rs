@@ -1,15 +1,28 @@
 use worker::*;
 
+pub mod allocator;
+pub mod auth;
+pub mod config;
+pub mod creation;
+pub mod crypto;
+pub mod destination;
+pub mod device;
+pub mod expiry;
+pub mod geo;
+pub mod id;
+pub mod idempotency;
+pub mod link;
+pub mod reserved;
+pub mod resolve;
+pub mod split;
 mod routes;
+pub mod store;
+pub mod template;
+pub mod time;
 
 #[event(fetch)]
 async fn main(req: Request, env: Env, _ctx: Context) -> Result<Response> {
-    Router::new()
-        .get_async("/", routes::home::get_handler)
-        .get_async("/create", routes::create::get_handler)
-        .post_async("/create", routes::create::post_handler)
-        .get_async("/:id", routes::redirect::get_handler)
-        .or_else_any_method_async("/*path", routes::not_found::handler)
+    routes::router()
         .run(req, env)
         .await
 }
@@ -0,0 +1,423 @@
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::PasswordHash;
+
+/// Current on-disk schema version for [`LinkRecord`].
+///
+/// Version 0 is the legacy format where the stored value was the bare destination URL.
+pub const SCHEMA_VERSION: u32 = 1;
+
+pub const DEFAULT_STATUS: u16 = 302;
+
+/// Redirect statuses a link may use. 307 and 308 make clients repeat the method and body.
+pub const REDIRECT_STATUSES: &[u16] = &[301, 302, 307, 308];
+
+pub fn status_label(status: u16) -> &'static str {
+    match status {
+        301 => "Moved Permanently",
+        302 => "Found",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        _ => "",
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinkRecord {
+    #[serde(rename = "v")]
+    pub version: u32,
+    pub destination: String,
+    #[serde(default = "default_status")]
+    pub status: u16,
+    /// What happens to the visitor's query string on redirect.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub query: QueryPolicy,
+    /// Country or continent specific destinations, used instead of `destination` when they match.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub geo: Vec<GeoRule>,
+    /// Platform specific destinations, e.g. the App Store for iOS; these beat `geo` rules.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub devices: Vec<DeviceRule>,
+    /// Weighted A/B destinations, used instead of `destination` when no device or location rule matches.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variants: Vec<Variant>,
+    pub owner: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+    /// Epoch millis after which the link stops redirecting.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// Number of redirects allowed before the link is exhausted; counted by the `IdAllocator`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_clicks: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<PasswordHash>,
+    #[serde(default)]
+    pub flags: LinkFlags,
+    /// Set when the link is soft-deleted; the record stays behind as a tombstone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted: Option<Tombstone>,
+    /// Append-only log of replaced destinations, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<HistoryEntry>,
+}
+
+/// How a visitor's query string is combined with the destination's own query.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryPolicy {
+    /// Discard the visitor's parameters.
+    #[default]
+    Drop,
+    /// Add the visitor's parameters after the destination's, keeping duplicates.
+    Append,
+    /// Add the visitor's parameters, but keep the destination's value when a key is in both.
+    MergeKeepDestination,
+    /// Add the visitor's parameters, replacing the destination's value when a key is in both.
+    MergePreferVisitor,
+}
+
+impl QueryPolicy {
+    pub const ALL: [QueryPolicy; 4] = [Self::Drop, Self::Append, Self::MergeKeepDestination, Self::MergePreferVisitor];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Drop => "drop",
+            Self::Append => "append",
+            Self::MergeKeepDestination => "merge_keep_destination",
+            Self::MergePreferVisitor => "merge_prefer_visitor",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|policy| policy.as_str() == value)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Drop => "Drop visitor parameters",
+            Self::Append => "Append visitor parameters",
+            Self::MergeKeepDestination => "Merge, destination wins on conflicts",
+            Self::MergePreferVisitor => "Merge, visitor wins on conflicts",
+        }
+    }
+}
+
+/// Where a [`GeoRule`] applies, as reported by Cloudflare for the visitor's request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Region {
+    /// ISO 3166-1 alpha-2 code, e.g. `DE`.
+    Country(String),
+    /// One of `AF`, `AN`, `AS`, `EU`, `NA`, `OC`, `SA`.
+    Continent(String),
+}
+
+/// Send visitors from `region` to `destination`, e.g. `{"country": "DE", "destination": "..."}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GeoRule {
+    #[serde(flatten)]
+    pub region: Region,
+    pub destination: String,
+}
+
+/// The kind of client following a link, as told apart by [`crate::device::classify`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Platform {
+    Ios,
+    Android,
+    Desktop,
+    /// Crawlers, link preview fetchers and command-line clients.
+    Bot,
+}
+
+impl Platform {
+    pub const ALL: [Platform; 4] = [Self::Ios, Self::Android, Self::Desktop, Self::Bot];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Ios => "ios",
+            Self::Android => "android",
+            Self::Desktop => "desktop",
+            Self::Bot => "bot",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Ios => "iOS",
+            Self::Android => "Android",
+            Self::Desktop => "Desktop",
+            Self::Bot => "Bots and link previews",
+        }
+    }
+}
+
+/// Send visitors on `platform` to `destination`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceRule {
+    pub platform: Platform,
+    pub destination: String,
+}
+
+/// One arm of an A/B split; the weights of a link's variants add up to 100.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Variant {
+    pub destination: String,
+    pub weight: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tombstone {
+    pub deleted_by: String,
+    pub deleted_at: u64,
+}
+
+/// A destination the link used to point at, and who replaced it when.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub destination: String,
+    pub changed_by: String,
+    pub changed_at: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LinkFlags {
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub disabled: bool,
+    /// Also answer `/<id>/<rest>`, forwarding `<rest>` onto the end of the destination path.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub prefix: bool,
+    /// Keep returning visitors on the A/B variant they were first sent to, with a cookie.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub sticky: bool,
+}
+
+/// KV metadata written alongside every link by the create handler.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LinkMetadata {
+    #[serde(default)]
+    pub created_by: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub destination: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub removed: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub protected: bool,
+}
+
+/// KV metadata is capped at 1024 bytes, so only a prefix of long destinations is kept there.
+const METADATA_DESTINATION_LIMIT: usize = 512;
+
+fn truncate(s: &str, max: usize) -> String {
+    let mut end = s.len().min(max);
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    s[..end].to_string()
+}
+
+fn default_status() -> u16 {
+    DEFAULT_STATUS
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+impl LinkRecord {
+    pub fn new(destination: String, owner: String, now: u64) -> Self {
+        Self {
+            version: SCHEMA_VERSION,
+            destination,
+            status: DEFAULT_STATUS,
+            query: QueryPolicy::default(),
+            geo: Vec::new(),
+            devices: Vec::new(),
+            variants: Vec::new(),
+            owner,
+            created_at: now,
+            updated_at: now,
+            expires_at: None,
+            max_clicks: None,
+            password: None,
+            flags: LinkFlags::default(),
+            deleted: None,
+            history: Vec::new(),
+        }
+    }
+
+    /// Parse a stored value, accepting both JSON records and legacy plain-string destinations.
+    ///
+    /// Returns the record together with `true` when the stored value is out of date and should be
+    /// rewritten (see [`LinkRecord::needs_migration`]).
+    pub fn from_stored(raw: &str, metadata: Option<&LinkMetadata>, legacy_created_at: u64) -> Option<(Self, bool)> {
+        let trimmed = raw.trim();
+        if trimmed.starts_with('{') {
+            let record: Self = serde_json::from_str(trimmed).ok()?;
+            let stale = record.needs_migration();
+            return Some((record.upgrade(), stale));
+        }
+
+        if trimmed.is_empty() {
+            return None;
+        }
+
+        let owner = metadata
+            .and_then(|m| m.created_by.clone())
+            .unwrap_or_default();
+        let mut record = Self::new(trimmed.to_string(), owner, legacy_created_at);
+        record.version = 0;
+        Some((record.upgrade(), true))
+    }
+
+    /// Point the link at a new destination, recording the one it replaces in `history`.
+    pub fn set_destination(&mut self, destination: String, changed_by: &str, now: u64) {
+        let previous = std::mem::replace(&mut self.destination, destination);
+        self.history.push(HistoryEntry {
+            destination: previous,
+            changed_by: changed_by.to_string(),
+            changed_at: now,
+        });
+        self.updated_at = now;
+    }
+
+    /// Whether the link has been disabled or soft-deleted and should answer 410 Gone.
+    pub fn is_removed(&self) -> bool {
+        self.flags.disabled || self.deleted.is_some()
+    }
+
+    /// Whether a soft-deleted link can still be restored, i.e. is inside the retention window.
+    pub fn is_restorable(&self, now: u64, retention_ms: u64) -> bool {
+        self.deleted.as_ref().is_some_and(|t| now < t.deleted_at.saturating_add(retention_ms))
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|at| at <= now)
+    }
+
+    /// Whether the link is a bare redirect to `destination`: no expiry, click limit or password,
+    /// no location, device or A/B rules, no prefix forwarding, the default query policy and
+    /// `default_status`. Only such a link can stand in for a request for a bare URL.
+    pub fn is_plain(&self, default_status: u16) -> bool {
+        self.expires_at.is_none()
+            && self.max_clicks.is_none()
+            && self.password.is_none()
+            && self.geo.is_empty()
+            && self.devices.is_empty()
+            && self.variants.is_empty()
+            && !self.flags.prefix
+            && self.query == QueryPolicy::default()
+            && self.status == default_status
+    }
+
+    pub fn needs_migration(&self) -> bool {
+        self.version < SCHEMA_VERSION
+    }
+
+    /// Bring a record from any older schema version up to [`SCHEMA_VERSION`].
+    pub fn upgrade(mut self) -> Self {
+        if self.version == 0 {
+            self.status = DEFAULT_STATUS;
+            self.version = 1;
+        }
+        self
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+
+    pub fn metadata(&self) -> LinkMetadata {
+        LinkMetadata {
+            created_by: Some(self.owner.clone()),
+            created_at: Some(self.created_at.to_string()),
+            destination: Some(truncate(&self.destination, METADATA_DESTINATION_LIMIT)),
+            expires_at: self.expires_at,
+            removed: self.is_removed(),
+            protected: self.password.is_some(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_stored_reads_json_records() {
+        let record = LinkRecord::new("https://example.com".into(), "ann@example.com".into(), 1000);
+        let json = record.to_json().unwrap();
+        assert_eq!(LinkRecord::from_stored(&format!(" {}\n", json), None, 0), Some((record, false)));
+
+        let (old, stale) = LinkRecord::from_stored(r#"{"v":0,"destination":"https://example.com","status":302,"owner":"","created_at":1,"updated_at":1}"#, None, 0).unwrap();
+        assert!(stale);
+        assert_eq!((old.version, old.status), (SCHEMA_VERSION, DEFAULT_STATUS));
+    }
+
+    #[test]
+    fn from_stored_upgrades_legacy_strings() {
+        let metadata = LinkMetadata { created_by: Some("ann@example.com".into()), ..LinkMetadata::default() };
+        let (record, stale) = LinkRecord::from_stored("https://example.com\n", Some(&metadata), 42).unwrap();
+        assert!(stale);
+        assert_eq!(record.destination, "https://example.com");
+        assert_eq!(record.owner, "ann@example.com");
+        assert_eq!((record.created_at, record.updated_at), (42, 42));
+        assert_eq!((record.version, record.status), (SCHEMA_VERSION, DEFAULT_STATUS));
+
+        let (record, stale) = LinkRecord::from_stored("https://example.com", None, 42).unwrap();
+        assert!(stale);
+        assert_eq!(record.owner, "");
+    }
+
+    #[test]
+    fn is_plain_checks_every_redirect_setting() {
+        let plain = LinkRecord::new("https://example.com".into(), "ann@example.com".into(), 1000);
+        assert!(plain.is_plain(DEFAULT_STATUS));
+        assert!(!plain.is_plain(301));
+
+        let changes: [fn(&mut LinkRecord); 9] = [
+            |r| r.expires_at = Some(2000),
+            |r| r.max_clicks = Some(5),
+            |r| r.password = Some(PasswordHash { salt: "c2FsdA".into(), hash: "aGFzaA".into(), rounds: 1 }),
+            |r| r.geo = vec![GeoRule { region: Region::Country("DE".into()), destination: "https://example.de".into() }],
+            |r| r.devices = vec![DeviceRule { platform: Platform::Ios, destination: "https://apps.apple.com".into() }],
+            |r| r.variants = vec![Variant { destination: "https://example.com/b".into(), weight: 100 }],
+            |r| r.flags.prefix = true,
+            |r| r.query = QueryPolicy::Append,
+            |r| r.status = 301,
+        ];
+        for (index, change) in changes.iter().enumerate() {
+            let mut record = plain.clone();
+            change(&mut record);
+            assert!(!record.is_plain(DEFAULT_STATUS), "change {}", index);
+        }
+    }
+
+    #[test]
+    fn metadata_flags_protected_links() {
+        let mut record = LinkRecord::new("https://example.com".into(), "ann@example.com".into(), 1000);
+        assert!(!record.metadata().protected);
+        record.password = Some(PasswordHash { salt: "c2FsdA".into(), hash: "aGFzaA".into(), rounds: 1 });
+        assert!(record.metadata().protected);
+        let json = serde_json::to_value(record.metadata()).unwrap();
+        assert_eq!(json["protected"], true);
+    }
+
+    #[test]
+    fn from_stored_rejects_empty_and_malformed_values() {
+        assert_eq!(LinkRecord::from_stored("", None, 0), None);
+        assert_eq!(LinkRecord::from_stored("  \n", None, 0), None);
+        assert_eq!(LinkRecord::from_stored("{not json", None, 0), None);
+        assert_eq!(LinkRecord::from_stored(r#"{"v":1}"#, None, 0), None);
+    }
+}
@@ -0,0 +1,96 @@
+use worker::Env;
+
+use crate::config;
+use crate::routes;
+
+/// Why a short name cannot be registered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reservation {
+    /// The name is the first segment of an application route and would never resolve.
+    Route,
+    /// The name matches the configured `RESERVED_NAMES` deny list; admins may override.
+    DenyList,
+}
+
+impl Reservation {
+    pub fn message(self, name: &str) -> String {
+        match self {
+            Self::Route => format!("Name '{}' is reserved by the application", name),
+            Self::DenyList => format!("Name '{}' is not allowed", name),
+        }
+    }
+}
+
+/// Reserved-name registry: application routes plus the `RESERVED_NAMES` deny list.
+///
+/// Deny list entries are comma-separated and may use a leading and/or trailing `*` to match
+/// suffixes, prefixes or substrings (e.g. `*badword*`).
+pub struct ReservedNames {
+    deny: Vec<String>,
+}
+
+impl ReservedNames {
+    pub fn new<I: IntoIterator<Item = String>>(deny: I) -> Self {
+        Self {
+            deny: deny.into_iter().map(|p| p.trim().to_lowercase()).filter(|p| !p.is_empty()).collect(),
+        }
+    }
+
+    pub fn from_env(env: &Env) -> Self {
+        Self::new(config::list(env, "RESERVED_NAMES"))
+    }
+
+    pub fn check(&self, name: &str) -> Option<Reservation> {
+        let name = name.to_lowercase();
+        if routes::route_segments().any(|segment| segment == name) {
+            return Some(Reservation::Route);
+        }
+        if self.deny.iter().any(|pattern| matches(pattern, &name)) {
+            return Some(Reservation::DenyList);
+        }
+        None
+    }
+}
+
+fn matches(pattern: &str, name: &str) -> bool {
+    match (pattern.strip_prefix('*'), pattern.strip_suffix('*')) {
+        (Some(rest), _) if rest.ends_with('*') => name.contains(&rest[..rest.len() - 1]),
+        (Some(suffix), _) => name.ends_with(suffix),
+        (None, Some(prefix)) => name.starts_with(prefix),
+        (None, None) => name == pattern,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_supports_wildcards() {
+        assert!(matches("acme", "acme"));
+        assert!(!matches("acme", "acme-docs"));
+        assert!(matches("acme*", "acme-docs"));
+        assert!(!matches("acme*", "my-acme"));
+        assert!(matches("*acme", "my-acme"));
+        assert!(!matches("*acme", "acme-docs"));
+        assert!(matches("*bad*", "notbadatall"));
+        assert!(matches("*bad*", "bad"));
+        assert!(!matches("*bad*", "good"));
+    }
+
+    #[test]
+    fn check_reserves_every_route_segment() {
+        let reserved = ReservedNames::new(Vec::new());
+        for name in ["create", "links", "tokens", "unlock", "api", "admin", "stats", "Links"] {
+            assert_eq!(reserved.check(name), Some(Reservation::Route), "{}", name);
+        }
+        assert_eq!(reserved.check("docs"), None);
+    }
+
+    #[test]
+    fn check_applies_the_deny_list_case_insensitively() {
+        let reserved = ReservedNames::new(vec![" Acme* ".to_string(), "".to_string()]);
+        assert_eq!(reserved.check("ACME-sale"), Some(Reservation::DenyList));
+        assert_eq!(reserved.check("sale"), None);
+    }
+}
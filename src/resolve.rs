@@ -0,0 +1,121 @@
+use url::form_urlencoded;
+use worker::Url;
+
+use crate::link::QueryPolicy;
+
+/// Append the path after a prefix link's name to `destination`'s path.
+///
+/// Returns `None` if `rest` contains `.` or `..` segments, which would otherwise be resolved
+/// against the destination and could climb out of its path.
+pub fn append_path(mut destination: Url, rest: &str) -> Option<Url> {
+    let dot_segment = |segment: &str| {
+        let decoded = segment.to_ascii_lowercase().replace("%2e", ".");
+        decoded == "." || decoded == ".."
+    };
+    if rest.split('/').any(dot_segment) {
+        return None;
+    }
+
+    let path = format!("{}/{}", destination.path().trim_end_matches('/'), rest.trim_start_matches('/'));
+    destination.set_path(&path);
+    Some(destination)
+}
+
+/// Combine the visitor's query string with `destination`'s according to `policy`.
+///
+/// The destination's query is kept byte for byte, so signed URLs stay valid; only the visitor's
+/// parameters are re-encoded, and only when merging.
+pub fn apply_query(policy: QueryPolicy, mut destination: Url, incoming: Option<&str>) -> Url {
+    let incoming = incoming.unwrap_or_default().trim_matches('&');
+    if incoming.is_empty() || policy == QueryPolicy::Drop {
+        return destination;
+    }
+
+    let own = destination.query().unwrap_or_default().trim_matches('&').to_string();
+    let joined = if policy == QueryPolicy::Append {
+        [own.as_str(), incoming].iter().filter(|q| !q.is_empty()).copied().collect::<Vec<_>>().join("&")
+    } else {
+        let key = |segment: &str| form_urlencoded::parse(segment.as_bytes()).next().map(|(k, _)| k.into_owned());
+        let own_segments: Vec<&str> = own.split('&').filter(|s| !s.is_empty()).collect();
+        let own_keys: Vec<String> = own_segments.iter().filter_map(|s| key(s)).collect();
+        let visitor: Vec<(String, String)> = form_urlencoded::parse(incoming.as_bytes()).into_owned().collect();
+        let visitor_wins = policy == QueryPolicy::MergePreferVisitor;
+
+        let kept_own = own_segments.into_iter()
+            .filter(|segment| !visitor_wins || key(segment).is_none_or(|k| !visitor.iter().any(|(vk, _)| *vk == k)));
+        let added = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(visitor.iter().filter(|(k, _)| visitor_wins || !own_keys.contains(k)))
+            .finish();
+        kept_own.chain(Some(added.as_str()).filter(|a| !a.is_empty())).collect::<Vec<_>>().join("&")
+    };
+
+    destination.set_query(Some(&joined));
+    destination
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(policy: QueryPolicy, destination: &str, incoming: Option<&str>) -> String {
+        apply_query(policy, Url::parse(destination).unwrap(), incoming).to_string()
+    }
+
+    fn append(destination: &str, rest: &str) -> Option<String> {
+        append_path(Url::parse(destination).unwrap(), rest).map(|url| url.to_string())
+    }
+
+    #[test]
+    fn append_path_joins_with_a_single_slash() {
+        assert_eq!(append("https://docs.example.com", "getting-started").unwrap(), "https://docs.example.com/getting-started");
+        assert_eq!(append("https://docs.example.com/v2/", "api/auth").unwrap(), "https://docs.example.com/v2/api/auth");
+        assert_eq!(append("https://github.com/org?tab=repos", "repo/issues").unwrap(), "https://github.com/org/repo/issues?tab=repos");
+    }
+
+    #[test]
+    fn append_path_rejects_dot_segments() {
+        assert_eq!(append("https://docs.example.com/v2", "../admin"), None);
+        assert_eq!(append("https://docs.example.com/v2", "a/%2E%2e/b"), None);
+        assert_eq!(append("https://docs.example.com/v2", "./a"), None);
+        assert!(append("https://docs.example.com/v2", "a..b/c").is_some());
+    }
+
+    #[test]
+    fn drop_ignores_visitor_query() {
+        assert_eq!(apply(QueryPolicy::Drop, "https://example.com/?a=1", Some("ref=x")), "https://example.com/?a=1");
+    }
+
+    #[test]
+    fn append_keeps_both_values() {
+        assert_eq!(
+            apply(QueryPolicy::Append, "https://example.com/p?a=1#top", Some("a=2&ref=news")),
+            "https://example.com/p?a=1&a=2&ref=news#top",
+        );
+    }
+
+    #[test]
+    fn merge_precedence_decides_conflicts() {
+        assert_eq!(
+            apply(QueryPolicy::MergeKeepDestination, "https://example.com/?a=1&b=2", Some("a=9&c=3")),
+            "https://example.com/?a=1&b=2&c=3",
+        );
+        assert_eq!(
+            apply(QueryPolicy::MergePreferVisitor, "https://example.com/?a=1&b=2", Some("a=9&c=3")),
+            "https://example.com/?b=2&a=9&c=3",
+        );
+    }
+
+    #[test]
+    fn destination_query_is_kept_verbatim() {
+        let signed = "https://example.com/file?flag&X-Sig=a%2Bb%20c";
+        assert_eq!(apply(QueryPolicy::Append, signed, Some("ref=news")), "https://example.com/file?flag&X-Sig=a%2Bb%20c&ref=news");
+        assert_eq!(apply(QueryPolicy::MergeKeepDestination, signed, Some("flag=1&ref=a b")), "https://example.com/file?flag&X-Sig=a%2Bb%20c&ref=a+b");
+        assert_eq!(apply(QueryPolicy::MergePreferVisitor, signed, Some("flag=1")), "https://example.com/file?X-Sig=a%2Bb%20c&flag=1");
+    }
+
+    #[test]
+    fn empty_visitor_query_leaves_destination_untouched() {
+        assert_eq!(apply(QueryPolicy::Append, "https://example.com/path", Some("")), "https://example.com/path");
+        assert_eq!(apply(QueryPolicy::Append, "https://example.com/path", None), "https://example.com/path");
+    }
+}
@@ -1,4 +1,126 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use qrcode::{QrCode, render::svg};
+use worker::{Error, Request, Result, Router};
+
+use crate::link::{DeviceRule, Platform};
+
 pub mod home;
 pub mod create;
 pub mod redirect;
 pub mod not_found;
+pub mod gone;
+pub mod unlock;
+pub mod edit;
+pub mod lifecycle;
+pub mod links;
+pub mod message;
+pub mod api;
+pub mod tokens;
+
+/// Declares every route once: [`router`] registers them and [`route_segments`] keeps their
+/// first path segments out of the short-name namespace.
+macro_rules! app_routes {
+    ($($method:ident $path:literal => $handler:path,)*) => {
+        const ROUTE_PATTERNS: &[&str] = &[$($path),*];
+
+        pub fn router<'a>() -> Router<'a, ()> {
+            Router::new()$(.$method($path, $handler))*
+        }
+    };
+}
+
+app_routes! {
+    get_async "/" => home::get_handler,
+    get_async "/create" => create::get_handler,
+    post_async "/create" => create::post_handler,
+    get_async "/links" => links::get_handler,
+    get_async "/links/:id" => edit::get_handler,
+    get_async "/links/:id/qr" => links::qr_handler,
+    post_async "/links/:id" => edit::post_handler,
+    post_async "/links/:id/rollback" => edit::rollback_handler,
+    post_async "/links/:id/disable" => lifecycle::disable_handler,
+    post_async "/links/:id/enable" => lifecycle::enable_handler,
+    post_async "/links/:id/delete" => lifecycle::delete_handler,
+    post_async "/links/:id/restore" => lifecycle::restore_handler,
+    get_async "/tokens" => tokens::get_handler,
+    post_async "/tokens" => tokens::post_handler,
+    post_async "/tokens/:id/revoke" => tokens::revoke_handler,
+    get_async "/api/v1/links" => api::list_handler,
+    post_async "/api/v1/links" => api::create_handler,
+    get_async "/api/v1/links/:id" => api::get_handler,
+    patch_async "/api/v1/links/:id" => api::patch_handler,
+    delete_async "/api/v1/links/:id" => api::delete_handler,
+    post_async "/unlock/:id" => unlock::post_handler,
+    on_async "/:id" => redirect::handler,
+    on_async "/:id/*rest" => redirect::prefix_handler,
+    or_else_any_method_async "/*path" => not_found::handler,
+}
+
+/// Segments held for planned routes, reserved before they are registered.
+const PLANNED_SEGMENTS: &[&str] = &["admin", "stats"];
+
+/// First path segment of every static route, plus [`PLANNED_SEGMENTS`]. Custom names equal to
+/// one of these would be shadowed by the router.
+pub fn route_segments() -> impl Iterator<Item = &'static str> {
+    ROUTE_PATTERNS.iter()
+        .filter_map(|pattern| pattern.trim_start_matches('/').split('/').next())
+        .filter(|segment| !segment.is_empty() && !segment.starts_with([':', '*']))
+        .chain(PLANNED_SEGMENTS.iter().copied())
+}
+
+pub fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+     .replace('<', "&lt;")
+     .replace('>', "&gt;")
+     .replace('"', "&quot;")
+     .replace('\'', "&#x27;")
+}
+
+/// Absolute short URL for `id` on the host the request came in on.
+pub fn short_url(req: &Request, id: &str) -> String {
+    let host = req.headers()
+        .get("host")
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| "localhost:8787".to_string());
+    let protocol = if host.contains("localhost") { "http" } else { "https" };
+    format!("{}://{}/{}", protocol, host, id)
+}
+
+pub fn qr_svg(text: &str) -> Result<String> {
+    let code = QrCode::new(text.as_bytes())
+        .map_err(|_| Error::from("Failed to generate QR code"))?;
+
+    Ok(code.render::<svg::Color>()
+        .min_dimensions(300, 300)
+        .dark_color(svg::Color("#000000"))
+        .light_color(svg::Color("#ffffff"))
+        .build())
+}
+
+/// One URL input per [`Platform`], named `device_<platform>` and pre-filled from `rules`.
+pub fn device_fields(rules: &[DeviceRule]) -> String {
+    Platform::ALL.iter()
+        .map(|&platform| {
+            let value = rules.iter().find(|r| r.platform == platform).map(|r| html_escape(&r.destination)).unwrap_or_default();
+            format!(
+                r#"<div class="device"><label for="device-{0}">{1}</label><input id="device-{0}" type="text" name="device_{0}" value="{2}" placeholder="Default destination"></div>"#,
+                platform.as_str(), platform.label(), value,
+            )
+        })
+        .collect()
+}
+
+/// Read the inputs written by [`device_fields`] through `field`, which looks up a submitted form
+/// field; empty ones are left for validation to drop.
+pub fn device_rules(field: impl Fn(&str) -> Option<String>) -> Vec<DeviceRule> {
+    Platform::ALL.iter()
+        .filter_map(|&platform| field(&format!("device_{}", platform.as_str())).map(|destination| DeviceRule { platform, destination }))
+        .collect()
+}
+
+/// `qr_svg` as a `data:` URI, for embedding in an `<img>` tag.
+pub fn qr_data_uri(svg: &str) -> String {
+    format!("data:image/svg+xml;base64,{}", STANDARD.encode(svg))
+}
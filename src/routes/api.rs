@@ -0,0 +1,375 @@
+use serde::{Deserialize, Serialize};
+use worker::*;
+use super::edit::{self, Editable};
+use super::lifecycle::{self, Action, Refusal};
+use super::short_url;
+use crate::auth::{self, AuthError, Permission, User};
+use crate::config;
+use crate::allocator::Allocator;
+use crate::creation::{self, CreateConfig, NewLink, Rejection};
+use crate::destination;
+use crate::device;
+use crate::geo;
+use crate::idempotency::{self, Check};
+use crate::link::{DeviceRule, GeoRule, LinkRecord, QueryPolicy, Variant, REDIRECT_STATUSES};
+use crate::split;
+use crate::store;
+
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 1000;
+
+/// A full link as returned by the single-link endpoints.
+#[derive(Serialize)]
+struct LinkView<'a> {
+    id: &'a str,
+    short_url: String,
+    /// Left out, with the other destinations, of password-protected links the caller cannot manage.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    destination: Option<&'a str>,
+    owner: &'a str,
+    status: u16,
+    query: QueryPolicy,
+    prefix: bool,
+    geo: &'a [GeoRule],
+    devices: &'a [DeviceRule],
+    variants: &'a [Variant],
+    sticky: bool,
+    /// Clicks per A/B variant, in the order of `variants`; only on `GET /api/v1/links/:id` with `stats:read`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    variant_clicks: Option<Vec<u64>>,
+    created_at: u64,
+    updated_at: u64,
+    expires_at: Option<u64>,
+    max_clicks: Option<u64>,
+    password_protected: bool,
+    disabled: bool,
+    deleted: bool,
+}
+
+impl<'a> LinkView<'a> {
+    fn new(req: &Request, id: &'a str, record: &'a LinkRecord) -> Self {
+        Self {
+            id,
+            short_url: short_url(req, id),
+            destination: Some(&record.destination),
+            owner: &record.owner,
+            status: record.status,
+            query: record.query,
+            prefix: record.flags.prefix,
+            geo: &record.geo,
+            devices: &record.devices,
+            variants: &record.variants,
+            sticky: record.flags.sticky,
+            variant_clicks: None,
+            created_at: record.created_at,
+            updated_at: record.updated_at,
+            expires_at: record.expires_at,
+            max_clicks: record.max_clicks,
+            password_protected: record.password.is_some(),
+            disabled: record.flags.disabled,
+            deleted: record.deleted.is_some(),
+        }
+    }
+
+    /// Drop every destination, so the view shows the link's settings but not where it leads.
+    fn redact(mut self) -> Self {
+        self.destination = None;
+        self.geo = &[];
+        self.devices = &[];
+        self.variants = &[];
+        self
+    }
+}
+
+/// Response to `POST /api/v1/links`: the link plus what deduplication found.
+#[derive(Serialize)]
+struct CreatedView<'a> {
+    #[serde(flatten)]
+    link: LinkView<'a>,
+    reused: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duplicate_of: Option<&'a str>,
+}
+
+/// A link as listed by `GET /api/v1/links`, built from the store's summary.
+#[derive(Serialize)]
+struct LinkItem<'a> {
+    id: &'a str,
+    short_url: String,
+    /// Left out of password-protected links the caller cannot manage, as in [`LinkView`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    destination: Option<&'a str>,
+    owner: &'a str,
+    created_at: u64,
+    removed: bool,
+    password_protected: bool,
+}
+
+impl LinkItem<'_> {
+    /// Drop the destination, so the item shows the link but not where it leads.
+    fn redact(mut self) -> Self {
+        self.destination = None;
+        self
+    }
+}
+
+#[derive(Serialize)]
+struct ListResponse<'a> {
+    links: Vec<LinkItem<'a>>,
+    /// Pass back as `?cursor=` for the next page; `null` once the listing is complete.
+    /// Pages are filtered per user, so one may hold fewer than `limit` links before the end.
+    cursor: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct ListQuery {
+    #[serde(default)]
+    cursor: Option<String>,
+    #[serde(default)]
+    limit: Option<usize>,
+    #[serde(default)]
+    scope: String,
+}
+
+/// Fields that `PATCH /api/v1/links/:id` may change; absent fields are left alone.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LinkPatch {
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    disabled: Option<bool>,
+    #[serde(default)]
+    status: Option<u16>,
+    #[serde(default)]
+    query: Option<QueryPolicy>,
+    #[serde(default)]
+    prefix: Option<bool>,
+    #[serde(default)]
+    geo: Option<Vec<GeoRule>>,
+    #[serde(default)]
+    devices: Option<Vec<DeviceRule>>,
+    #[serde(default)]
+    variants: Option<Vec<Variant>>,
+    #[serde(default)]
+    sticky: Option<bool>,
+}
+
+/// `{"error": {"code": ..., "message": ...}}` with the given status.
+pub fn error(status: u16, code: &str, message: &str) -> Result<Response> {
+    let body = serde_json::json!({ "error": { "code": code, "message": message } });
+    Ok(Response::from_json(&body)?.with_status(status))
+}
+
+fn rejected(rejection: &Rejection) -> Result<Response> {
+    error(rejection.status, rejection.code, &rejection.message)
+}
+
+pub fn auth_failed(error: &AuthError) -> Result<Response> {
+    let code = if error.status == 401 { "unauthorized" } else { "forbidden" };
+    self::error(error.status, code, error.message)
+}
+
+fn not_found(id: &str) -> Result<Response> {
+    error(404, "not_found", &format!("No link named '{}'", id))
+}
+
+async fn load_editable(ctx: &RouteContext<()>, user: &User, id: &str) -> Result<std::result::Result<LinkRecord, Response>> {
+    let store = store::from_env(&ctx.env)?;
+    Ok(match edit::load_editable(store.as_ref(), id, user).await? {
+        Editable::Allowed(record) => Ok(*record),
+        Editable::Forbidden => Err(error(403, "forbidden", edit::FORBIDDEN)?),
+        Editable::Missing => Err(not_found(id)?),
+    })
+}
+
+pub async fn list_handler(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let user = match auth::require(&req, &ctx.env, Permission::ReadLinks).await? {
+        Ok(user) => user,
+        Err(error) => return auth_failed(&error),
+    };
+    let query: ListQuery = match req.query() {
+        Ok(query) => query,
+        Err(_) => return error(400, "invalid_query", "Query parameters are malformed"),
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    // Same visibility as the dashboard
+    let show_all = user.lists_all_links(query.scope == "all");
+
+    let page = store::from_env(&ctx.env)?.list(query.cursor, limit).await?;
+    let body = ListResponse {
+        links: page.links.iter()
+            .filter(|l| show_all || l.owner.eq_ignore_ascii_case(&user.email))
+            .map(|l| {
+                let item = LinkItem {
+                    id: &l.id,
+                    short_url: short_url(&req, &l.id),
+                    destination: Some(&l.destination),
+                    owner: &l.owner,
+                    created_at: l.created_at,
+                    removed: l.removed,
+                    password_protected: l.protected,
+                };
+                if l.protected && !user.can_manage(&l.owner) { item.redact() } else { item }
+            })
+            .collect(),
+        cursor: page.cursor,
+    };
+    Response::from_json(&body)
+}
+
+pub async fn create_handler(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let user = match auth::require(&req, &ctx.env, Permission::CreateLinks).await? {
+        Ok(user) => user,
+        Err(error) => return auth_failed(&error),
+    };
+
+    let pending = match idempotency::check(&ctx.env, &req, &user.email).await? {
+        Check::Untracked => None,
+        Check::Pending(pending) => Some(pending),
+        Check::Replay(response) => return Ok(response),
+        Check::Rejected(rejection) => return rejected(&rejection),
+    };
+    let response = create(&mut req, &ctx.env, &user).await;
+    match pending {
+        Some(pending) => pending.finish(response).await,
+        None => response,
+    }
+}
+
+async fn create(req: &mut Request, env: &Env, user: &User) -> Result<Response> {
+    let Ok(input) = req.json::<NewLink>().await else {
+        return error(400, "invalid_body", "Request body must be a JSON object");
+    };
+    let store = store::from_env(env)?;
+    let allocator = Allocator::from_env(env)?;
+    match creation::create(store.as_ref(), Some(&allocator), &CreateConfig::from_env(env)?, user, input, Date::now().as_millis()).await? {
+        Ok(created) => {
+            let body = CreatedView {
+                link: LinkView::new(req, &created.id, &created.record),
+                reused: created.reused,
+                duplicate_of: created.duplicate_of.as_deref(),
+            };
+            Ok(Response::from_json(&body)?.with_status(if created.reused { 200 } else { 201 }))
+        },
+        Err(rejection) => rejected(&rejection),
+    }
+}
+
+pub async fn get_handler(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let user = match auth::require(&req, &ctx.env, Permission::ReadLinks).await? {
+        Ok(user) => user,
+        Err(error) => return auth_failed(&error),
+    };
+    let id = ctx.param("id").unwrap().to_string();
+    match store::from_env(&ctx.env)?.get(&id).await? {
+        // Same visibility as the listing; others' links look missing rather than forbidden
+        Some(record) if !user.lists_all_links(true) && !record.owner.eq_ignore_ascii_case(&user.email) => not_found(&id),
+        Some(record) => {
+            let mut view = LinkView::new(&req, &id, &record);
+            if record.password.is_some() && !user.can_manage(&record.owner) {
+                view = view.redact();
+            }
+            if user.can(Permission::ViewStats) && !view.variants.is_empty() {
+                view.variant_clicks = Some(edit::variant_clicks(&ctx.env, &id, &record).await?);
+            }
+            Response::from_json(&view)
+        },
+        None => not_found(&id),
+    }
+}
+
+pub async fn patch_handler(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let user = match auth::authenticate(&req, &ctx.env).await? {
+        Ok(user) => user,
+        Err(error) => return auth_failed(&error),
+    };
+    let id = ctx.param("id").unwrap().to_string();
+    let patch: LinkPatch = match req.json().await {
+        Ok(patch) => patch,
+        Err(_) => return error(400, "invalid_body", "Request body must be a JSON object with only 'url', 'status', 'query', 'prefix', 'geo', 'devices', 'variants', 'sticky' and 'disabled'"),
+    };
+    let mut record = match load_editable(&ctx, &user, &id).await? {
+        Ok(record) => record,
+        Err(response) => return Ok(response),
+    };
+    if record.deleted.is_some() {
+        return error(409, "link_deleted", "Restore the link before editing it");
+    }
+
+    let now = Date::now().as_millis();
+    if let Some(url) = patch.url {
+        let url = match destination::normalize(&url) {
+            Ok(url) => url,
+            Err(message) => return rejected(&Rejection::invalid(message)),
+        };
+        if url != record.destination {
+            record.set_destination(url, &user.email, now);
+        }
+    }
+    if let Some(status) = patch.status {
+        if !REDIRECT_STATUSES.contains(&status) {
+            return rejected(&Rejection::invalid("Redirect status must be 301, 302, 307 or 308"));
+        }
+        record.status = status;
+        record.updated_at = now;
+    }
+    if let Some(query) = patch.query {
+        record.query = query;
+        record.updated_at = now;
+    }
+    if let Some(prefix) = patch.prefix {
+        record.flags.prefix = prefix;
+        record.updated_at = now;
+    }
+    if let Some(rules) = patch.geo {
+        record.geo = match geo::validate(rules) {
+            Ok(rules) => rules,
+            Err(message) => return rejected(&Rejection::invalid(message)),
+        };
+        record.updated_at = now;
+    }
+    if let Some(rules) = patch.devices {
+        record.devices = match device::validate(rules) {
+            Ok(rules) => rules,
+            Err(message) => return rejected(&Rejection::invalid(message)),
+        };
+        record.updated_at = now;
+    }
+    if let Some(variants) = patch.variants {
+        record.variants = match split::validate(variants) {
+            Ok(variants) => variants,
+            Err(message) => return rejected(&Rejection::invalid(message)),
+        };
+        record.updated_at = now;
+    }
+    if let Some(sticky) = patch.sticky {
+        record.flags.sticky = sticky;
+        record.updated_at = now;
+    }
+    if let Some(disabled) = patch.disabled {
+        let action = if disabled { Action::Disable } else { Action::Enable };
+        if let Err(reason) = lifecycle::transition(&mut record, action, &user.email, now, config::delete_retention_ms(&ctx.env)) {
+            return error(409, "invalid_transition", reason);
+        }
+    }
+
+    store::from_env(&ctx.env)?.update(&id, &record).await?;
+    Response::from_json(&LinkView::new(&req, &id, &record))
+}
+
+pub async fn delete_handler(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let user = match auth::authenticate(&req, &ctx.env).await? {
+        Ok(user) => user,
+        Err(error) => return auth_failed(&error),
+    };
+    let id = ctx.param("id").unwrap().to_string();
+    let store = store::from_env(&ctx.env)?;
+    let now = Date::now().as_millis();
+    match lifecycle::change(store.as_ref(), &id, &user, Action::Delete, now, config::delete_retention_ms(&ctx.env)).await? {
+        Ok(_) => Ok(Response::empty()?.with_status(204)),
+        Err(Refusal::Missing) => not_found(&id),
+        Err(Refusal::Forbidden) => error(403, "forbidden", edit::FORBIDDEN),
+        Err(Refusal::Conflict(reason)) => error(409, "invalid_transition", reason),
+    }
+}
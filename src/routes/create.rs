@@ -1,182 +1,157 @@
 use worker::*;
-use qrcode::{QrCode, render::svg};
+use super::{api, device_fields, device_rules, html_escape, message, qr_data_uri, qr_svg, short_url};
+use crate::allocator::Allocator;
+use crate::auth::{self, Permission, User};
+use crate::creation::{self, CreateConfig, Created, NewLink, Rejection};
+use crate::geo;
+use crate::split;
+use crate::idempotency::{self, Check};
+use crate::link::{self, QueryPolicy};
+use crate::store;
 
-fn html_escape(s: &str) -> String {
-    s.replace('&', "&amp;")
-     .replace('<', "&lt;")
-     .replace('>', "&gt;")
-     .replace('"', "&quot;")
-     .replace('\'', "&#x27;")
-}
-
-fn generate_short_id() -> String {
-    const CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
-    let timestamp = Date::now().as_millis() as u64;
-    let mut id = String::new();
-    let mut num = timestamp;
-    for _ in 0..6 {
-        id.push(CHARSET[(num % CHARSET.len() as u64) as usize] as char);
-        num /= CHARSET.len() as u64;
+pub async fn get_handler(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    if let Err(error) = auth::require(&req, &ctx.env, Permission::CreateLinks).await? {
+        return message::auth_failed(&error);
     }
-    id
-}
-
-pub async fn get_handler(_req: Request, _ctx: RouteContext<()>) -> Result<Response> {
-    let html = r#"
-<!DOCTYPE html>
-<html>
-<head>
-    <title>EdgeLink - Create URL Redirect</title>
-    <style>
-        body { font-family: system-ui; max-width: 600px; margin: 50px auto; padding: 20px; background: #f9f9f9; }
-        .container { background: white; padding: 30px; border-radius: 8px; box-shadow: 0 2px 4px rgba(0,0,0,0.1); }
-        h1 { color: #24292f; margin: 0 0 25px 0; font-size: 24px; }
-        .field { margin-bottom: 20px; }
-        .field label { display: block; font-weight: 600; color: #333; margin-bottom: 6px; font-size: 14px; }
-        input { width: 100%; box-sizing: border-box; padding: 8px 12px; border: 1px solid #d0d7de; border-radius: 4px; font-size: 14px; font-family: system-ui; }
-        input:focus { outline: none; border-color: #0969da; box-shadow: 0 0 0 3px rgba(9,105,218,0.1); }
-        button { width: 100%; padding: 10px; background: #0969da; color: white; border: none; border-radius: 4px; cursor: pointer; font-size: 14px; font-weight: 600; transition: background 0.2s; }
-        button:hover { background: #0860ca; }
-        button:active { background: #0757ba; }
-    </style>
-</head>
-<body>
-    <div id="container" class="container">
-        <h1 id="title">⚡ Create URL Redirect and QR Code</h1>
-        <form id="create-form" method="POST" action="/create">
-            <div id="name-field" class="field">
-                <label id="name-label" for="name">Custom name (optional)</label>
-                <input id="name" type="text" name="name" placeholder="Leave blank for auto-generated" maxlength="20">
-            </div>
-            <div id="url-field" class="field">
-                <label id="url-label" for="url">URL</label>
-                <input id="url" type="text" name="url" placeholder="example.com or https://example.com" required>
-            </div>
-            <button id="submit-btn" type="submit">Generate Link</button>
-        </form>
-    </div>
-</body>
-</html>"#;
 
-    Response::from_html(html)
+    render_form(None)
 }
 
 pub async fn post_handler(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
-    let email = req.headers()
-        .get("cf-access-authenticated-user-email")
-        .ok()
-        .flatten()
-        .unwrap_or_else(|| "local@example.com".to_string());
-
-    let form = match req.form_data().await {
-        Ok(form) => form,
-        Err(_) => return render_error("Invalid form data"),
+    let json = wants_json(&req);
+    let user = match auth::require(&req, &ctx.env, Permission::CreateLinks).await? {
+        Ok(user) => user,
+        Err(error) if json => return api::auth_failed(&error),
+        Err(error) => return message::auth_failed(&error),
     };
 
-    let url = match form.get("url") {
-        Some(worker::FormEntry::Field(url)) => url,
-        _ => return render_error("URL is required"),
+    let pending = match idempotency::check(&ctx.env, &req, &user.email).await? {
+        Check::Untracked => None,
+        Check::Pending(pending) => Some(pending),
+        Check::Replay(response) => return Ok(response),
+        Check::Rejected(rejection) => return Ok(reject(json, &rejection)?.with_status(rejection.status)),
     };
+    let response = respond(&mut req, &ctx.env, &user, json).await;
+    match pending {
+        Some(pending) => pending.finish(response).await,
+        None => response,
+    }
+}
+
+fn reject(json: bool, rejection: &Rejection) -> Result<Response> {
+    if json {
+        api::error(rejection.status, rejection.code, &rejection.message)
+    } else {
+        render_error(&rejection.message)
+    }
+}
 
-    // Get optional custom name (auto-normalize: lowercase, spaces to hyphens, remove invalid chars)
-    let custom_name = match form.get("name") {
-        Some(worker::FormEntry::Field(name)) => {
-            let normalized: String = name.trim()
-                .to_lowercase()
-                .replace(' ', "-")
-                .chars()
-                .filter(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || *c == '-')
-                .collect();
-            if normalized.is_empty() {
-                None
-            } else {
-                Some(normalized)
-            }
+async fn respond(req: &mut Request, env: &Env, user: &User, json: bool) -> Result<Response> {
+    let created = match read_input(req).await {
+        Ok(input) => {
+            let store = store::from_env(env)?;
+            let allocator = Allocator::from_env(env)?;
+            creation::create(store.as_ref(), Some(&allocator), &CreateConfig::from_env(env)?, user, input, Date::now().as_millis()).await?
         },
-        _ => None,
+        Err(rejection) => Err(rejection),
+    };
+    let Created { id: short_id, record, reused, duplicate_of } = match created {
+        Ok(created) => created,
+        Err(rejection) => return reject(json, &rejection),
     };
 
-    if url.is_empty() {
-        return render_error("URL is required");
+    let short_url = short_url(req, &short_id);
+    let qr_svg = qr_svg(&short_url)?;
+
+    if json {
+        let body = serde_json::json!({
+            "id": short_id,
+            "short_url": short_url,
+            "destination": record.destination,
+            "qr_svg": qr_svg,
+            "qr_data_uri": qr_data_uri(&qr_svg),
+            "reused": reused,
+            "duplicate_of": duplicate_of,
+        });
+        return Ok(Response::from_json(&body)?.with_status(if reused { 200 } else { 201 }));
     }
 
-    // Auto-prepend https:// if no protocol specified
-    let url = if !url.starts_with("http://") && !url.starts_with("https://") {
-        format!("https://{}", url)
+    let notice = if reused {
+        Some("This URL was already shortened, so here is the existing link.".to_string())
     } else {
-        url
+        duplicate_of.map(|id| format!("This URL is already shortened as /{}", id))
     };
+    render_success(&short_id, &short_url, &record.destination, &qr_svg, notice.as_deref())
+}
 
-    // Validate URL format and domain structure
-    let parsed_url = match Url::parse(&url) {
-        Ok(u) => u,
-        Err(_) => return render_error("Invalid URL format"),
-    };
+/// Whether the client asked for a JSON response with `Accept: application/json`.
+fn wants_json(req: &Request) -> bool {
+    req.headers().get("accept").ok().flatten().is_some_and(|accept| accept.contains("application/json"))
+}
 
-    // Ensure URL has a valid host with at least a domain extension
-    match parsed_url.host_str() {
-        Some(host) => {
-            if !host.contains('.') {
-                return render_error("URL must include a domain extension (e.g., .com, .org)");
-            }
-        },
-        None => return render_error("URL must include a valid domain"),
+/// Read the create request from a JSON body or from form data, depending on `Content-Type`.
+async fn read_input(req: &mut Request) -> std::result::Result<NewLink, Rejection> {
+    let content_type = req.headers().get("content-type").ok().flatten().unwrap_or_default();
+    if content_type.starts_with("application/json") {
+        return req.json().await.map_err(|_| Rejection::new(400, "invalid_body", "Request body must be a JSON object"));
     }
 
-    let kv = ctx.env.kv("edgelink")?;
-
-    // Determine short ID: use custom name if provided and valid, otherwise generate
-    let short_id = if let Some(name) = custom_name {
-        // Validate custom name length
-        if name.len() < 2 || name.len() > 20 {
-            return render_error("Custom name must be 2-20 characters");
-        }
-
-        // Check if name already exists
-        if kv.get(&name).text().await?.is_some() {
-            return render_error(&format!("Name '{}' is already taken", name));
-        }
-
-        name
-    } else {
-        // Auto-generate ID with collision detection
-        loop {
-            let id = generate_short_id();
-            if kv.get(&id).text().await?.is_none() {
-                break id;
-            }
-        }
+    let form = req.form_data().await.map_err(|_| Rejection::new(400, "invalid_body", "Invalid form data"))?;
+    let field = |name: &str| match form.get(name) {
+        Some(worker::FormEntry::Field(value)) => Some(value),
+        _ => None,
     };
 
-    kv.put(&short_id, &url)?
-        .metadata(serde_json::json!({
-            "created_by": email,
-            "created_at": Date::now().to_string(),
-        }))?
-        .execute()
-        .await?;
-
-    let host = req.headers()
-        .get("host")
-        .ok()
-        .flatten()
-        .unwrap_or_else(|| "localhost:8787".to_string());
-    let protocol = if host.contains("localhost") { "http" } else { "https" };
+    let max_clicks = match field("max_clicks").filter(|v| !v.trim().is_empty()) {
+        Some(value) => Some(value.trim().parse::<u64>()
+            .map_err(|_| Rejection::invalid("Click limit must be a positive whole number"))?),
+        None => None,
+    };
+    let status = match field("status").filter(|v| !v.trim().is_empty()) {
+        Some(value) => Some(value.trim().parse::<u16>()
+            .map_err(|_| Rejection::invalid("Redirect status must be 301, 302, 307 or 308"))?),
+        None => None,
+    };
+    let query = match field("query").filter(|v| !v.is_empty()) {
+        Some(value) => QueryPolicy::parse(&value).ok_or_else(|| Rejection::invalid("Unknown query string policy"))?,
+        None => QueryPolicy::Drop,
+    };
+    let geo = geo::parse(&field("geo").unwrap_or_default()).map_err(Rejection::invalid)?;
+    let variants = split::parse(&field("variants").unwrap_or_default()).map_err(Rejection::invalid)?;
+    Ok(NewLink {
+        url: field("url").unwrap_or_default(),
+        name: field("name"),
+        expires: field("expires"),
+        max_clicks,
+        password: field("password"),
+        allow_reserved: field("allow_reserved").is_some_and(|v| v == "on"),
+        status,
+        query,
+        prefix: field("prefix").is_some_and(|v| v == "on"),
+        geo,
+        devices: device_rules(field),
+        variants,
+        sticky: field("sticky").is_some_and(|v| v == "on"),
+    })
+}
 
-    let short_url = format!("{}://{}/{}", protocol, host, short_id);
-    let code = QrCode::new(short_url.as_bytes())
-        .map_err(|_| Error::from("Failed to generate QR code"))?;
+fn render_error(message: &str) -> Result<Response> {
+    render_form(Some(message))
+}
 
-    let qr_svg = code.render::<svg::Color>()
-        .min_dimensions(300, 300)
-        .dark_color(svg::Color("#000000"))
-        .light_color(svg::Color("#ffffff"))
-        .build();
+/// The create form, with `error` shown above it after a failed submission.
+fn render_form(error: Option<&str>) -> Result<Response> {
+    let error_html = error
+        .map(|e| format!(r#"<div id="error-message" class="error">{}</div>"#, html_escape(e)))
+        .unwrap_or_default();
+    let status_options: String = link::REDIRECT_STATUSES.iter()
+        .map(|status| format!(r#"<option value="{0}">{0} {1}</option>"#, status, link::status_label(*status)))
+        .collect();
 
-    render_success(&short_url, &url, &qr_svg)
-}
+    let query_options: String = QueryPolicy::ALL.iter()
+        .map(|policy| format!(r#"<option value="{}">{}</option>"#, policy.as_str(), policy.label()))
+        .collect();
 
-fn render_error(message: &str) -> Result<Response> {
     let html = format!(r#"
 <!DOCTYPE html>
 <html>
@@ -188,8 +163,12 @@ fn render_error(message: &str) -> Result<Response> {
         h1 {{ color: #24292f; margin: 0 0 25px 0; font-size: 24px; }}
         .field {{ margin-bottom: 20px; }}
         .field label {{ display: block; font-weight: 600; color: #333; margin-bottom: 6px; font-size: 14px; }}
-        input {{ width: 100%; box-sizing: border-box; padding: 8px 12px; border: 1px solid #d0d7de; border-radius: 4px; font-size: 14px; font-family: system-ui; }}
-        input:focus {{ outline: none; border-color: #0969da; box-shadow: 0 0 0 3px rgba(9,105,218,0.1); }}
+        input, select, textarea {{ width: 100%; box-sizing: border-box; padding: 8px 12px; border: 1px solid #d0d7de; border-radius: 4px; font-size: 14px; font-family: system-ui; }}
+        .devices {{ display: grid; grid-template-columns: 1fr 1fr; gap: 10px; }}
+        .devices label {{ font-weight: normal; color: #57606a; font-size: 13px; margin-bottom: 4px; }}
+        .field.checkbox label {{ font-weight: normal; color: #57606a; }}
+        .field.checkbox input {{ width: auto; margin-right: 6px; }}
+        input:focus, select:focus, textarea:focus {{ outline: none; border-color: #0969da; box-shadow: 0 0 0 3px rgba(9,105,218,0.1); }}
         button {{ width: 100%; padding: 10px; background: #0969da; color: white; border: none; border-radius: 4px; cursor: pointer; font-size: 14px; font-weight: 600; transition: background 0.2s; }}
         button:hover {{ background: #0860ca; }}
         button:active {{ background: #0757ba; }}
@@ -199,7 +178,7 @@ fn render_error(message: &str) -> Result<Response> {
 <body>
     <div id="container" class="container">
         <h1 id="title">⚡ Create URL Redirect and QR Code</h1>
-        <div id="error-message" class="error">{}</div>
+        {}
         <form id="create-form" method="POST" action="/create">
             <div id="name-field" class="field">
                 <label id="name-label" for="name">Custom name (optional)</label>
@@ -207,18 +186,66 @@ fn render_error(message: &str) -> Result<Response> {
             </div>
             <div id="url-field" class="field">
                 <label id="url-label" for="url">URL</label>
-                <input id="url" type="text" name="url" placeholder="example.com or https://example.com" required>
+                <input id="url" type="text" name="url" placeholder="example.com, or https://jira.example.com/browse/{1} for a template" required>
+            </div>
+            <div id="expires-field" class="field">
+                <label id="expires-label" for="expires">Expires (optional)</label>
+                <input id="expires" type="text" name="expires" placeholder="7d, 12h or 2025-12-31T18:00 (UTC)">
+            </div>
+            <div id="max-clicks-field" class="field">
+                <label id="max-clicks-label" for="max_clicks">Click limit (optional)</label>
+                <input id="max_clicks" type="number" name="max_clicks" min="1" placeholder="1 for a one-time link">
+            </div>
+            <div id="password-field" class="field">
+                <label id="password-label" for="password">Password (optional)</label>
+                <input id="password" type="password" name="password" placeholder="Visitors must enter this to continue" autocomplete="new-password">
+            </div>
+            <div id="status-field" class="field">
+                <label id="status-label" for="status">Redirect type</label>
+                <select id="status" name="status">
+                    <option value="">Deployment default</option>
+                    {}
+                </select>
             </div>
-            <button id="submit-btn" type="submit">Generate QR Code</button>
+            <div id="query-field" class="field">
+                <label id="query-label" for="query">Visitor query string</label>
+                <select id="query" name="query">{}</select>
+            </div>
+            <div id="devices-field" class="field">
+                <label id="devices-label">Destinations by device (optional)</label>
+                <div class="devices">{}</div>
+            </div>
+            <div id="geo-field" class="field">
+                <label id="geo-label" for="geo">Location rules (optional)</label>
+                <textarea id="geo" name="geo" rows="3" placeholder="One per line, e.g.&#10;DE https://shop.example.de&#10;continent:EU https://shop.example.eu"></textarea>
+            </div>
+            <div id="variants-field" class="field">
+                <label id="variants-label" for="variants">A/B split (optional)</label>
+                <textarea id="variants" name="variants" rows="3" placeholder="Weight and URL per line, adding up to 100, e.g.&#10;70 https://example.com/a&#10;30 https://example.com/b"></textarea>
+            </div>
+            <div id="sticky-field" class="field checkbox">
+                <label id="sticky-label"><input id="sticky" type="checkbox" name="sticky"> Keep returning visitors on the same A/B destination</label>
+            </div>
+            <div id="prefix-field" class="field checkbox">
+                <label id="prefix-label"><input id="prefix" type="checkbox" name="prefix"> Prefix link: also forward <code>/name/any/path</code> to the destination plus that path</label>
+            </div>
+            <div id="allow-reserved-field" class="field checkbox">
+                <label id="allow-reserved-label"><input id="allow-reserved" type="checkbox" name="allow_reserved"> Allow reserved name (admins only)</label>
+            </div>
+            <button id="submit-btn" type="submit">Generate Link</button>
         </form>
     </div>
 </body>
-</html>"#, html_escape(message));
+</html>"#, error_html, status_options, query_options, device_fields(&[]));
 
     Response::from_html(html)
 }
 
-fn render_success(short_url: &str, destination_url: &str, qr_svg: &str) -> Result<Response> {
+fn render_success(short_id: &str, short_url: &str, destination_url: &str, qr_svg: &str, notice: Option<&str>) -> Result<Response> {
+    let notice_html = notice
+        .map(|n| format!(r#"<div id="notice" class="notice">{}</div>"#, html_escape(n)))
+        .unwrap_or_default();
+    let escaped_id = html_escape(short_id);
     let escaped_short = html_escape(short_url);
     let escaped_dest = html_escape(destination_url);
 
@@ -248,12 +275,14 @@ fn render_success(short_url: &str, destination_url: &str, qr_svg: &str) -> Resul
         .qr-container {{ flex-shrink: 0; position: relative; width: 200px; height: 200px; }}
         .qr-container .qr-code {{ width: 100%; height: 100%; border: 1px solid #d0d7de; box-sizing: border-box; border-radius: 4px; background: white; padding: 15px; }}
         .qr-container .qr-code svg {{ width: 100%; height: 100%; display: block; }}
-        .actions {{ margin-top: 25px; padding-top: 20px; border-top: 1px solid #eee; }}
+        .actions {{ display: flex; justify-content: space-between; margin-top: 25px; padding-top: 20px; border-top: 1px solid #eee; }}
         .actions a {{ color: #0066cc; text-decoration: none; font-weight: 500; }}
         .actions a:hover {{ text-decoration: underline; }}
+        .notice {{ background: #fff8c5; border: 1px solid #d4a72c; color: #7d4e00; padding: 12px; border-radius: 4px; font-size: 14px; }}
     </style>
 </head>
 <body>
+    {}
     <div id="container" class="container">
         <div id="info" class="info">
             <div id="url-field" class="field">
@@ -272,6 +301,8 @@ fn render_success(short_url: &str, destination_url: &str, qr_svg: &str) -> Resul
             </div>
             <div id="actions" class="actions">
                 <a id="create-another" href="/create">← Create another</a>
+                <a id="edit-link" href="/links/{}">Edit link</a>
+                <a id="my-links" href="/links">My links →</a>
             </div>
         </div>
         <div id="qr-container" class="qr-container">
@@ -344,7 +375,7 @@ fn render_success(short_url: &str, destination_url: &str, qr_svg: &str) -> Resul
         }}
     </script>
 </body>
-</html>"#, escaped_short, escaped_short, escaped_short, escaped_dest, escaped_dest, escaped_dest, qr_svg);
+</html>"#, notice_html, escaped_short, escaped_short, escaped_short, escaped_dest, escaped_dest, escaped_dest, escaped_id, qr_svg);
 
     Response::from_html(html)
 }
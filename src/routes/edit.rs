@@ -0,0 +1,462 @@
+use worker::*;
+use super::{device_fields, device_rules, html_escape, message, not_found};
+use crate::allocator::Allocator;
+use crate::auth::{self, User};
+use crate::config;
+use crate::destination;
+use crate::device;
+use crate::geo;
+use crate::link::{self, LinkRecord, QueryPolicy, REDIRECT_STATUSES};
+use crate::split;
+use crate::store::{self, LinkStore};
+use crate::time;
+
+pub enum Editable {
+    Allowed(Box<LinkRecord>),
+    Forbidden,
+    Missing,
+}
+
+/// Load `id` and check that the signed-in user may edit it: its creator (unless a viewer) or an admin.
+pub async fn load_editable(store: &dyn LinkStore, id: &str, user: &User) -> Result<Editable> {
+    Ok(match store.get(id).await? {
+        Some(record) if user.can_manage(&record.owner) => Editable::Allowed(Box::new(record)),
+        Some(_) => Editable::Forbidden,
+        None => Editable::Missing,
+    })
+}
+
+/// Clicks counted for each of the link's A/B variants, in the order of `record.variants`.
+pub async fn variant_clicks(env: &Env, id: &str, record: &LinkRecord) -> Result<Vec<u64>> {
+    if record.variants.is_empty() {
+        return Ok(Vec::new());
+    }
+    let keys: Vec<String> = record.variants.iter().map(split::key).collect();
+    Allocator::from_env(env)?.variant_clicks(id, &keys).await
+}
+
+pub const FORBIDDEN: &str = "Only the creator of this link or an admin can change it, and viewers cannot change links.";
+
+pub async fn get_handler(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let id = ctx.param("id").unwrap().to_string();
+    let user = match auth::authenticate(&req, &ctx.env).await? {
+        Ok(user) => user,
+        Err(error) => return message::auth_failed(&error),
+    };
+    let store = store::from_env(&ctx.env)?;
+    match load_editable(store.as_ref(), &id, &user).await? {
+        Editable::Allowed(record) => {
+            let clicks = variant_clicks(&ctx.env, &id, &record).await?;
+            render(&id, &record, config::delete_retention_ms(&ctx.env), None, Some(&clicks))
+        },
+        Editable::Forbidden => message::forbidden(FORBIDDEN),
+        Editable::Missing => not_found::handler(req, ctx).await,
+    }
+}
+
+pub async fn post_handler(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let id = ctx.param("id").unwrap().to_string();
+    let user = match auth::authenticate(&req, &ctx.env).await? {
+        Ok(user) => user,
+        Err(error) => return message::auth_failed(&error),
+    };
+    let store = store::from_env(&ctx.env)?;
+    let mut record = match load_editable(store.as_ref(), &id, &user).await? {
+        Editable::Allowed(record) => *record,
+        Editable::Forbidden => return message::forbidden(FORBIDDEN),
+        Editable::Missing => return not_found::handler(req, ctx).await,
+    };
+    if record.deleted.is_some() {
+        return render_page(&id, &record, config::delete_retention_ms(&ctx.env), Some("Restore the link before editing it"));
+    }
+
+    let form = req.form_data().await.ok();
+    let field = |name: &str| form.as_ref().and_then(|f| f.get_field(name));
+    match apply_form(&mut record, field, &user.email, Date::now().as_millis()) {
+        Ok(true) => {
+            store.update(&id, &record).await?;
+        },
+        Ok(false) => {},
+        Err(message) => return render_page(&id, &record, config::delete_retention_ms(&ctx.env), Some(&message)),
+    }
+    redirect_to_page(&req, &id)
+}
+
+/// Apply a submitted edit form to `record`, where `field` looks up a submitted field. Settings
+/// whose inputs are missing from the submission are left alone; since unchecked boxes are not
+/// submitted at all, a box counts only when the form lists it in its `checkboxes` field.
+/// Nothing is changed unless every field is valid. Returns whether the record changed.
+fn apply_form(record: &mut LinkRecord, field: impl Fn(&str) -> Option<String>, email: &str, now: u64) -> std::result::Result<bool, String> {
+    let url = match field("url") {
+        Some(url) if url.trim().is_empty() => return Err("URL is required".to_string()),
+        Some(url) => destination::normalize(&url)?,
+        None => record.destination.clone(),
+    };
+    let status = match field("status").map(|s| s.parse::<u16>()) {
+        Some(Ok(status)) if REDIRECT_STATUSES.contains(&status) => status,
+        None => record.status,
+        Some(_) => return Err("Redirect status must be 301, 302, 307 or 308".to_string()),
+    };
+    let query = match field("query").map(|q| QueryPolicy::parse(&q)) {
+        Some(Some(query)) => query,
+        None => record.query,
+        Some(None) => return Err("Unknown query string policy".to_string()),
+    };
+    let geo = match field("geo") {
+        Some(text) => geo::parse(&text)?,
+        None => record.geo.clone(),
+    };
+    let submitted_devices = device_rules(&field);
+    let devices = if submitted_devices.is_empty() { record.devices.clone() } else { device::validate(submitted_devices)? };
+    let variants = match field("variants") {
+        Some(text) => split::parse(&text)?,
+        None => record.variants.clone(),
+    };
+    let checkboxes = field("checkboxes").unwrap_or_default();
+    let checkbox = |name: &str, current: bool| {
+        if checkboxes.split_whitespace().any(|listed| listed == name) { field(name).is_some_and(|v| v == "on") } else { current }
+    };
+    let prefix = checkbox("prefix", record.flags.prefix);
+    let sticky = checkbox("sticky", record.flags.sticky);
+
+    let mut changed = false;
+    if url != record.destination {
+        record.set_destination(url, email, now);
+        changed = true;
+    }
+    if status != record.status || query != record.query || prefix != record.flags.prefix || geo != record.geo || devices != record.devices
+        || variants != record.variants || sticky != record.flags.sticky {
+        record.status = status;
+        record.query = query;
+        record.geo = geo;
+        record.devices = devices;
+        record.variants = variants;
+        record.flags.sticky = sticky;
+        record.flags.prefix = prefix;
+        record.updated_at = now;
+        changed = true;
+    }
+    Ok(changed)
+}
+
+pub async fn rollback_handler(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let id = ctx.param("id").unwrap().to_string();
+    let user = match auth::authenticate(&req, &ctx.env).await? {
+        Ok(user) => user,
+        Err(error) => return message::auth_failed(&error),
+    };
+    let store = store::from_env(&ctx.env)?;
+    let mut record = match load_editable(store.as_ref(), &id, &user).await? {
+        Editable::Allowed(record) => *record,
+        Editable::Forbidden => return message::forbidden(FORBIDDEN),
+        Editable::Missing => return not_found::handler(req, ctx).await,
+    };
+    if record.deleted.is_some() {
+        return render_page(&id, &record, config::delete_retention_ms(&ctx.env), Some("Restore the link before editing it"));
+    }
+
+    let entry = match req.form_data().await.ok().and_then(|form| form.get("index")) {
+        Some(worker::FormEntry::Field(index)) => index.parse::<usize>().ok().and_then(|i| record.history.get(i)).cloned(),
+        _ => None,
+    };
+    let Some(entry) = entry else {
+        return render_page(&id, &record, config::delete_retention_ms(&ctx.env), Some("Unknown history entry"));
+    };
+
+    if entry.destination != record.destination {
+        record.set_destination(entry.destination, &user.email, Date::now().as_millis());
+        store.update(&id, &record).await?;
+    }
+    redirect_to_page(&req, &id)
+}
+
+pub fn redirect_to_page(req: &Request, id: &str) -> Result<Response> {
+    let mut url = req.url()?;
+    url.set_path(&format!("/links/{}", id));
+    url.set_query(None);
+    Response::redirect_with_status(url, 303)
+}
+
+fn render_page(id: &str, record: &LinkRecord, retention_ms: u64, error: Option<&str>) -> Result<Response> {
+    render(id, record, retention_ms, error, None)
+}
+
+/// The edit page; `clicks` are the A/B variant counts, only looked up when the page is first shown.
+fn render(id: &str, record: &LinkRecord, retention_ms: u64, error: Option<&str>, clicks: Option<&[u64]>) -> Result<Response> {
+    let escaped_id = html_escape(id);
+    let status_html = render_status(&escaped_id, record, retention_ms);
+    let error_html = error
+        .map(|e| format!(r#"<div id="error-message" class="error">{}</div>"#, html_escape(e)))
+        .unwrap_or_default();
+
+    let status_options: String = REDIRECT_STATUSES.iter()
+        .map(|&status| format!(
+            r#"<option value="{0}"{1}>{0} {2}</option>"#,
+            status,
+            if status == record.status { " selected" } else { "" },
+            link::status_label(status),
+        ))
+        .collect();
+
+    let query_options: String = QueryPolicy::ALL.iter()
+        .map(|&policy| format!(
+            r#"<option value="{}"{}>{}</option>"#,
+            policy.as_str(),
+            if policy == record.query { " selected" } else { "" },
+            policy.label(),
+        ))
+        .collect();
+
+    let history_rows: String = record.history.iter()
+        .enumerate()
+        .rev()
+        .map(|(index, entry)| format!(r#"
+                <tr>
+                    <td class="dest"><code>{}</code></td>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td><form method="POST" action="/links/{}/rollback"><input type="hidden" name="index" value="{}"><button type="submit" class="secondary">Roll back</button></form></td>
+                </tr>"#,
+            html_escape(&entry.destination),
+            html_escape(&entry.changed_by),
+            time::format_utc(entry.changed_at),
+            escaped_id,
+            index,
+        ))
+        .collect();
+    let total_clicks: u64 = clicks.unwrap_or_default().iter().sum();
+    let variant_rows: String = record.variants.iter()
+        .enumerate()
+        .map(|(index, variant)| {
+            let count = clicks.and_then(|c| c.get(index).copied());
+            let share = match count {
+                Some(count) if total_clicks > 0 => format!("{:.1}%", count as f64 * 100.0 / total_clicks as f64),
+                _ => "–".to_string(),
+            };
+            format!(r#"
+                <tr>
+                    <td class="dest"><code>{}</code></td>
+                    <td>{}%</td>
+                    <td>{}</td>
+                    <td>{}</td>
+                </tr>"#,
+                html_escape(&variant.destination),
+                variant.weight,
+                count.map_or_else(|| "–".to_string(), |c| c.to_string()),
+                share,
+            )
+        })
+        .collect();
+    let split_html = if variant_rows.is_empty() {
+        String::new()
+    } else {
+        format!(r#"<div id="split" class="container">
+        <h2 id="split-title">A/B split</h2>
+        <table id="split-table">
+            <tr><th>Destination</th><th>Weight</th><th>Clicks</th><th>Share of clicks</th></tr>{}
+        </table>
+    </div>"#, variant_rows)
+    };
+
+    let history_html = if history_rows.is_empty() {
+        r#"<p id="history-empty" class="muted">The destination has never been changed.</p>"#.to_string()
+    } else {
+        format!(r#"<table id="history-table">
+                <tr><th>Previous destination</th><th>Changed by</th><th>Changed at</th><th></th></tr>{}
+            </table>"#, history_rows)
+    };
+
+    let html = format!(r#"
+<!DOCTYPE html>
+<html>
+<head>
+    <title>EdgeLink - Edit /{}</title>
+    <style>
+        body {{ font-family: system-ui; max-width: 800px; margin: 50px auto; padding: 20px; background: #f9f9f9; }}
+        .container {{ background: white; padding: 30px; border-radius: 8px; box-shadow: 0 2px 4px rgba(0,0,0,0.1); margin-bottom: 20px; }}
+        h1 {{ color: #24292f; margin: 0 0 25px 0; font-size: 24px; }}
+        h2 {{ color: #24292f; margin: 0 0 15px 0; font-size: 18px; }}
+        .field {{ margin-bottom: 20px; }}
+        .field label {{ display: block; font-weight: 600; color: #333; margin-bottom: 6px; font-size: 14px; }}
+        .devices {{ display: grid; grid-template-columns: 1fr 1fr; gap: 10px; }}
+        .devices label {{ font-weight: normal; color: #57606a; font-size: 13px; margin-bottom: 4px; }}
+        .field.checkbox label {{ font-weight: normal; color: #57606a; }}
+        .field.checkbox input {{ width: auto; margin-right: 6px; }}
+        input, select, textarea {{ width: 100%; box-sizing: border-box; padding: 8px 12px; border: 1px solid #d0d7de; border-radius: 4px; font-size: 14px; font-family: system-ui; }}
+        input:focus, select:focus, textarea:focus {{ outline: none; border-color: #0969da; box-shadow: 0 0 0 3px rgba(9,105,218,0.1); }}
+        button {{ width: 100%; padding: 10px; background: #0969da; color: white; border: none; border-radius: 4px; cursor: pointer; font-size: 14px; font-weight: 600; transition: background 0.2s; }}
+        button:hover {{ background: #0860ca; }}
+        button.secondary {{ width: auto; padding: 4px 10px; background: white; color: #24292f; border: 1px solid #d0d7de; font-weight: 500; }}
+        button.secondary:hover {{ background: #f6f8fa; }}
+        table {{ width: 100%; border-collapse: collapse; font-size: 13px; }}
+        th, td {{ text-align: left; padding: 8px; border-bottom: 1px solid #eee; vertical-align: middle; }}
+        td.dest {{ max-width: 300px; overflow: hidden; text-overflow: ellipsis; white-space: nowrap; }}
+        td form {{ margin: 0; }}
+        .muted {{ color: #57606a; font-size: 14px; }}
+        .lifecycle {{ display: flex; gap: 10px; margin-top: 15px; }}
+        .lifecycle form {{ margin: 0; }}
+        button.danger {{ color: #d1242f; border-color: #ff8182; }}
+        .error {{ background: #ffebe9; border: 1px solid #ff8182; color: #d1242f; padding: 12px; border-radius: 4px; margin-bottom: 20px; font-size: 14px; }}
+        .actions a {{ color: #0066cc; text-decoration: none; font-weight: 500; }}
+        .actions a:hover {{ text-decoration: underline; }}
+    </style>
+</head>
+<body>
+    <div id="container" class="container">
+        <h1 id="title">✏️ Edit /{}</h1>
+        {}
+        <form id="edit-form" method="POST" action="/links/{}">
+            <div id="url-field" class="field">
+                <label id="url-label" for="url">Destination</label>
+                <input id="url" type="text" name="url" value="{}" required>
+            </div>
+            <div id="redirect-status-field" class="field">
+                <label id="redirect-status-label" for="redirect-status">Redirect type</label>
+                <select id="redirect-status" name="status">{}</select>
+            </div>
+            <div id="query-field" class="field">
+                <label id="query-label" for="query">Visitor query string</label>
+                <select id="query" name="query">{}</select>
+            </div>
+            <div id="devices-field" class="field">
+                <label id="devices-label">Destinations by device</label>
+                <div class="devices">{}</div>
+            </div>
+            <div id="geo-field" class="field">
+                <label id="geo-label" for="geo">Location rules</label>
+                <textarea id="geo" name="geo" rows="3" placeholder="One per line, e.g.&#10;DE https://shop.example.de&#10;continent:EU https://shop.example.eu">{}</textarea>
+            </div>
+            <div id="variants-field" class="field">
+                <label id="variants-label" for="variants">A/B split</label>
+                <textarea id="variants" name="variants" rows="3" placeholder="Weight and URL per line, adding up to 100, e.g.&#10;70 https://example.com/a&#10;30 https://example.com/b">{}</textarea>
+            </div>
+            <div id="sticky-field" class="field checkbox">
+                <label id="sticky-label"><input id="sticky" type="checkbox" name="sticky"{}> Keep returning visitors on the same A/B destination</label>
+            </div>
+            <div id="prefix-field" class="field checkbox">
+                <label id="prefix-label"><input id="prefix" type="checkbox" name="prefix"{}> Prefix link: also forward <code>/{}/any/path</code> to the destination plus that path</label>
+            </div>
+            <input type="hidden" name="checkboxes" value="sticky prefix">
+            <button id="submit-btn" type="submit">Update Link</button>
+        </form>
+    </div>
+    <div id="status" class="container">
+        <h2 id="status-title">Status</h2>
+        {}
+    </div>
+    {}
+    <div id="history" class="container">
+        <h2 id="history-title">History</h2>
+        {}
+    </div>
+    <div id="actions" class="actions">
+        <a id="create-another" href="/create">← Create another</a>
+    </div>
+</body>
+</html>"#, escaped_id, escaped_id, error_html, escaped_id, html_escape(&record.destination), status_options, query_options, device_fields(&record.devices), html_escape(&geo::format(&record.geo)), html_escape(&split::format(&record.variants)), if record.flags.sticky { " checked" } else { "" },
+        if record.flags.prefix { " checked" } else { "" }, escaped_id, status_html, split_html, history_html);
+
+    Ok(Response::from_html(html)?.with_status(if error.is_some() { 400 } else { 200 }))
+}
+
+fn render_status(escaped_id: &str, record: &LinkRecord, retention_ms: u64) -> String {
+    let button = |action: &str, label: &str, class: &str| format!(
+        r#"<form method="POST" action="/links/{}/{}"><button id="{}-btn" type="submit" class="{}">{}</button></form>"#,
+        escaped_id, action, action, class, label,
+    );
+
+    match &record.deleted {
+        Some(tombstone) => {
+            let deadline = tombstone.deleted_at.saturating_add(retention_ms);
+            let restore = if record.is_restorable(Date::now().as_millis(), retention_ms) {
+                format!(r#"<div class="lifecycle">{}</div>"#, button("restore", "Restore", "secondary"))
+            } else {
+                String::new()
+            };
+            format!(
+                r#"<p id="status-text" class="muted">Deleted by {} on {}. It can be restored until {}, after which the name may be registered again.</p>{}"#,
+                html_escape(&tombstone.deleted_by),
+                time::format_utc(tombstone.deleted_at),
+                time::format_utc(deadline),
+                restore,
+            )
+        },
+        None if record.flags.disabled => format!(
+            r#"<p id="status-text" class="muted">Disabled - visitors see a "link removed" page.</p><div class="lifecycle">{}{}</div>"#,
+            button("enable", "Enable", "secondary"),
+            button("delete", "Delete", "secondary danger"),
+        ),
+        None => format!(
+            r#"<p id="status-text" class="muted">Active</p><div class="lifecycle">{}{}</div>"#,
+            button("disable", "Disable", "secondary"),
+            button("delete", "Delete", "secondary danger"),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::link::{DeviceRule, Platform, Variant};
+
+    const NOW: u64 = 1_700_000_000_000;
+
+    fn record() -> LinkRecord {
+        let mut record = LinkRecord::new("https://example.com".into(), "ann@example.com".into(), 0);
+        record.devices = vec![DeviceRule { platform: Platform::Ios, destination: "https://apps.apple.com/app/id1".into() }];
+        record.variants = vec![
+            Variant { destination: "https://example.com/a".into(), weight: 50 },
+            Variant { destination: "https://example.com/b".into(), weight: 50 },
+        ];
+        record.flags.prefix = true;
+        record.flags.sticky = true;
+        record
+    }
+
+    fn submit(record: &mut LinkRecord, fields: &[(&str, &str)]) -> std::result::Result<bool, String> {
+        let field = |name: &str| fields.iter().find(|(k, _)| *k == name).map(|(_, v)| v.to_string());
+        apply_form(record, field, "bob@example.com", NOW)
+    }
+
+    #[test]
+    fn apply_form_keeps_settings_missing_from_the_submission() {
+        let mut record = record();
+        assert_eq!(submit(&mut record, &[("url", "https://example.com"), ("status", "301")]), Ok(true));
+        assert_eq!(record.status, 301);
+        assert_eq!(record.devices, self::record().devices);
+        assert_eq!(record.variants, self::record().variants);
+        assert!(record.flags.prefix && record.flags.sticky);
+
+        let mut record = self::record();
+        assert_eq!(submit(&mut record, &[]), Ok(false));
+        assert_eq!(record, self::record());
+    }
+
+    #[test]
+    fn apply_form_clears_submitted_empty_inputs() {
+        let mut record = record();
+        let fields = [
+            ("url", "https://example.com"), ("device_ios", ""), ("device_android", ""), ("variants", ""),
+            ("checkboxes", "sticky prefix"), ("prefix", "on"),
+        ];
+        assert_eq!(submit(&mut record, &fields), Ok(true));
+        assert!(record.devices.is_empty() && record.variants.is_empty());
+        assert!(record.flags.prefix);
+        assert!(!record.flags.sticky);
+        assert_eq!(record.updated_at, NOW);
+    }
+
+    #[test]
+    fn apply_form_changes_nothing_when_a_field_is_invalid() {
+        let mut record = record();
+        assert!(submit(&mut record, &[("url", "https://example.org"), ("status", "200")]).is_err());
+        assert!(submit(&mut record, &[("url", " ")]).is_err());
+        assert!(submit(&mut record, &[("geo", "XYZ https://example.de")]).is_err());
+        assert_eq!(record, self::record());
+
+        assert_eq!(submit(&mut record, &[("url", "example.org")]), Ok(true));
+        assert_eq!(record.destination, "https://example.org");
+        assert_eq!(record.history[0].destination, "https://example.com");
+        assert_eq!(record.history[0].changed_by, "bob@example.com");
+    }
+}
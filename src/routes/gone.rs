@@ -0,0 +1,18 @@
+use worker::*;
+use super::message;
+
+pub fn expired() -> Result<Response> {
+    render("Link Expired", "This link has expired and no longer redirects.")
+}
+
+pub fn exhausted() -> Result<Response> {
+    render("Link Exhausted", "This link has reached its maximum number of uses and no longer redirects.")
+}
+
+pub fn removed() -> Result<Response> {
+    render("Link Removed", "This link has been removed by its owner and no longer redirects.")
+}
+
+fn render(title: &str, text: &str) -> Result<Response> {
+    message::render(410, "Gone", title, text)
+}
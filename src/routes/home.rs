@@ -1,7 +1,13 @@
 use worker::*;
+use crate::auth::{self, Permission};
 
-pub async fn get_handler(req: Request, _ctx: RouteContext<()>) -> Result<Response> {
+pub async fn get_handler(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    // Viewers cannot create links, so send them to the dashboard instead
+    let can_create = match auth::authenticate(&req, &ctx.env).await? {
+        Ok(user) => user.can(Permission::CreateLinks),
+        Err(_) => true,
+    };
     let mut url = req.url()?;
-    url.set_path("/create");
+    url.set_path(if can_create { "/create" } else { "/links" });
     Response::redirect(url)
-} 
\ No newline at end of file
+}
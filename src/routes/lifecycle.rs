@@ -0,0 +1,172 @@
+use worker::*;
+use super::edit::{self, Editable};
+use super::{message, not_found};
+use crate::auth::{self, User};
+use crate::config;
+use crate::link::{LinkRecord, Tombstone};
+use crate::store::{self, LinkStore};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Action {
+    Disable,
+    Enable,
+    Delete,
+    Restore,
+}
+
+pub async fn disable_handler(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    apply(req, ctx, Action::Disable).await
+}
+
+pub async fn enable_handler(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    apply(req, ctx, Action::Enable).await
+}
+
+pub async fn delete_handler(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    apply(req, ctx, Action::Delete).await
+}
+
+pub async fn restore_handler(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    apply(req, ctx, Action::Restore).await
+}
+
+/// Why [`change`] left a link as it was.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Refusal {
+    Missing,
+    Forbidden,
+    /// The action does not apply in the link's current state.
+    Conflict(&'static str),
+}
+
+async fn apply(req: Request, ctx: RouteContext<()>, action: Action) -> Result<Response> {
+    let id = ctx.param("id").unwrap().to_string();
+    let user = match auth::authenticate(&req, &ctx.env).await? {
+        Ok(user) => user,
+        Err(error) => return message::auth_failed(&error),
+    };
+    let store = store::from_env(&ctx.env)?;
+    let now = Date::now().as_millis();
+    match change(store.as_ref(), &id, &user, action, now, config::delete_retention_ms(&ctx.env)).await? {
+        Ok(_) => edit::redirect_to_page(&req, &id),
+        Err(Refusal::Missing) => not_found::handler(req, ctx).await,
+        Err(Refusal::Forbidden) => message::forbidden(edit::FORBIDDEN),
+        Err(Refusal::Conflict(reason)) => message::render(409, "Conflict", "Cannot change link", reason),
+    }
+}
+
+/// Load `id`, apply `action` on behalf of `user` and store the changed record.
+pub async fn change(
+    store: &dyn LinkStore,
+    id: &str,
+    user: &User,
+    action: Action,
+    now: u64,
+    retention_ms: u64,
+) -> Result<std::result::Result<LinkRecord, Refusal>> {
+    let mut record = match edit::load_editable(store, id, user).await? {
+        Editable::Allowed(record) => *record,
+        Editable::Forbidden => return Ok(Err(Refusal::Forbidden)),
+        Editable::Missing => return Ok(Err(Refusal::Missing)),
+    };
+    if let Err(reason) = transition(&mut record, action, &user.email, now, retention_ms) {
+        return Ok(Err(Refusal::Conflict(reason)));
+    }
+    store.update(id, &record).await?;
+    Ok(Ok(record))
+}
+
+pub fn transition(record: &mut LinkRecord, action: Action, email: &str, now: u64, retention_ms: u64) -> std::result::Result<(), &'static str> {
+    match action {
+        Action::Disable | Action::Enable | Action::Delete if record.deleted.is_some() => {
+            return Err("This link has been deleted; restore it first.");
+        },
+        Action::Disable => record.flags.disabled = true,
+        Action::Enable => record.flags.disabled = false,
+        Action::Delete => {
+            record.deleted = Some(Tombstone {
+                deleted_by: email.to_string(),
+                deleted_at: now,
+            });
+        },
+        Action::Restore => {
+            if !record.is_restorable(now, retention_ms) {
+                return Err("This link is not deleted or is past its restore window.");
+            }
+            record.deleted = None;
+        },
+    }
+    record.updated_at = now;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Role;
+    use crate::store::memory::MemoryLinkStore;
+    use futures_executor::block_on;
+
+    const NOW: u64 = 1_700_000_000_000;
+    /// `DELETE_RETENTION_DAYS` at its default of 30.
+    const RETENTION_MS: u64 = 30 * 24 * 60 * 60 * 1000;
+
+    fn record() -> LinkRecord {
+        LinkRecord::new("https://example.com".into(), "ann@example.com".into(), 0)
+    }
+
+    fn deleted_at(at: u64) -> LinkRecord {
+        let mut record = record();
+        transition(&mut record, Action::Delete, "ann@example.com", at, RETENTION_MS).unwrap();
+        record
+    }
+
+    #[test]
+    fn restore_only_inside_the_retention_window() {
+        let mut record = deleted_at(NOW - RETENTION_MS + 1);
+        assert_eq!(transition(&mut record, Action::Restore, "ann@example.com", NOW, RETENTION_MS), Ok(()));
+        assert_eq!((record.deleted.as_ref(), record.updated_at), (None, NOW));
+
+        let mut record = deleted_at(NOW - RETENTION_MS);
+        assert!(transition(&mut record, Action::Restore, "ann@example.com", NOW, RETENTION_MS).is_err());
+        assert!(record.deleted.is_some());
+    }
+
+    #[test]
+    fn restore_needs_a_deleted_link() {
+        let mut record = record();
+        transition(&mut record, Action::Disable, "ann@example.com", NOW, RETENTION_MS).unwrap();
+        assert!(record.flags.disabled);
+        assert!(transition(&mut record, Action::Restore, "ann@example.com", NOW, RETENTION_MS).is_err());
+        assert!(record.flags.disabled);
+    }
+
+    #[test]
+    fn deleted_links_refuse_everything_but_restore() {
+        let mut record = deleted_at(NOW);
+        let tombstone = record.deleted.clone();
+        for action in [Action::Delete, Action::Disable, Action::Enable] {
+            assert!(transition(&mut record, action, "bob@example.com", NOW + 1, RETENTION_MS).is_err(), "{:?}", action);
+        }
+        assert_eq!(record.deleted, tombstone);
+        assert_eq!(record.updated_at, NOW);
+    }
+
+    #[test]
+    fn change_checks_permissions_and_stores_the_result() {
+        let store = MemoryLinkStore::new();
+        block_on(store.put_if_absent("docs", &record())).unwrap();
+        let user = |email: &str, role| User { email: email.into(), groups: Vec::new(), role, scopes: None };
+
+        let apply = |id: &str, user: &User, action| block_on(change(&store, id, user, action, NOW, RETENTION_MS)).unwrap().map(|_| ());
+        let ann = user("ann@example.com", Role::Creator);
+        assert_eq!(apply("docs", &user("bob@example.com", Role::Creator), Action::Delete), Err(Refusal::Forbidden));
+        assert_eq!(apply("nope", &ann, Action::Delete), Err(Refusal::Missing));
+
+        assert_eq!(apply("docs", &ann, Action::Delete), Ok(()));
+        assert!(block_on(store.get("docs")).unwrap().unwrap().deleted.is_some());
+        assert!(matches!(apply("docs", &ann, Action::Delete), Err(Refusal::Conflict(_))));
+        assert_eq!(apply("docs", &user("bob@example.com", Role::Admin), Action::Restore), Ok(()));
+        assert!(block_on(store.get("docs")).unwrap().unwrap().deleted.is_none());
+    }
+}
@@ -0,0 +1,236 @@
+use worker::*;
+use super::{html_escape, message, not_found, qr_svg, short_url};
+use crate::auth::{self, Permission, User};
+use crate::store::{self, LinkStore, LinkSummary};
+use crate::time;
+
+const PAGE_SIZE: usize = 25;
+/// Upper bound on store pages scanned per dashboard view, to stay within request limits.
+const MAX_STORE_PAGES: usize = 20;
+const STORE_PAGE_SIZE: usize = 1000;
+
+#[derive(serde::Deserialize, Default)]
+struct Query {
+    #[serde(default)]
+    q: String,
+    #[serde(default)]
+    sort: String,
+    #[serde(default)]
+    page: Option<usize>,
+    /// `all` lists every user's links; only honoured for admins, viewers always see everything.
+    #[serde(default)]
+    scope: String,
+}
+
+/// Every link owned by `owner` (or every link when `None`), collected by paging through the store.
+/// The flag is `false` when the scan stopped at `MAX_STORE_PAGES` before reaching the end, so
+/// links stored further on are missing.
+async fn collect_links(store: &dyn LinkStore, owner: Option<&str>) -> Result<(Vec<LinkSummary>, bool)> {
+    let mut links = Vec::new();
+    let mut cursor = None;
+    for _ in 0..MAX_STORE_PAGES {
+        let page = store.list(cursor, STORE_PAGE_SIZE).await?;
+        links.extend(page.links.into_iter().filter(|l| owner.is_none_or(|o| l.owner.eq_ignore_ascii_case(o))));
+        match page.cursor {
+            Some(next) => cursor = Some(next),
+            None => return Ok((links, true)),
+        }
+    }
+    Ok((links, false))
+}
+
+pub async fn get_handler(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let user = match auth::require(&req, &ctx.env, Permission::ReadLinks).await? {
+        Ok(user) => user,
+        Err(error) => return message::auth_failed(&error),
+    };
+    let query: Query = req.query().unwrap_or_default();
+    let store = store::from_env(&ctx.env)?;
+
+    let show_all = user.lists_all_links(query.scope == "all");
+    let owner = if show_all { None } else { Some(user.email.as_str()) };
+
+    let needle = query.q.trim().to_lowercase();
+    let (links, complete) = collect_links(store.as_ref(), owner).await?;
+    let mut links: Vec<LinkSummary> = links.into_iter()
+        .filter(|l| needle.is_empty()
+            || l.id.to_lowercase().contains(&needle)
+            || (shows_destination(&user, l) && l.destination.to_lowercase().contains(&needle)))
+        .collect();
+
+    let oldest_first = query.sort == "oldest";
+    links.sort_by(|a, b| if oldest_first { a.created_at.cmp(&b.created_at) } else { b.created_at.cmp(&a.created_at) });
+
+    let pages = links.len().div_ceil(PAGE_SIZE).max(1);
+    let page = query.page.unwrap_or(1).clamp(1, pages);
+    let visible = links.iter().skip((page - 1) * PAGE_SIZE).take(PAGE_SIZE);
+
+    render(&req, &user, &query, show_all, oldest_first, visible, links.len(), page, pages, complete)
+}
+
+/// Password-protected destinations are only shown to those who can manage the link.
+fn shows_destination(user: &User, link: &LinkSummary) -> bool {
+    !link.protected || user.can_manage(&link.owner)
+}
+
+pub async fn qr_handler(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    if let Err(error) = auth::require(&req, &ctx.env, Permission::ViewStats).await? {
+        return message::auth_failed(&error);
+    }
+    let id = ctx.param("id").unwrap().to_string();
+    if store::from_env(&ctx.env)?.get(&id).await?.is_none() {
+        return not_found::handler(req, ctx).await;
+    }
+
+    let mut response = Response::ok(qr_svg(&short_url(&req, &id))?)?;
+    response.headers_mut().set("Content-Type", "image/svg+xml")?;
+    Ok(response)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn render<'a>(
+    req: &Request,
+    user: &User,
+    query: &Query,
+    show_all: bool,
+    oldest_first: bool,
+    links: impl Iterator<Item = &'a LinkSummary>,
+    total: usize,
+    page: usize,
+    pages: usize,
+    complete: bool,
+) -> Result<Response> {
+    let rows: String = links
+        .map(|link| {
+            let id = html_escape(&link.id);
+            let badge = if link.removed { r#" <span class="badge">removed</span>"# } else { "" };
+            let destination = if shows_destination(user, link) {
+                format!("<code>{}</code>", html_escape(&link.destination))
+            } else {
+                r#"<span class="muted">protected</span>"#.to_string()
+            };
+            let owner = if show_all { format!("<td>{}</td>", html_escape(&link.owner)) } else { String::new() };
+            let manage = if user.can_manage(&link.owner) {
+                format!(r#"
+                    <a href="/links/{}">Edit</a>
+                    <form method="POST" action="/links/{}/delete" onsubmit="return confirm('Delete /{}?')"><button type="submit" class="link danger">Delete</button></form>"#,
+                    id, id, id,
+                )
+            } else {
+                String::new()
+            };
+            format!(r#"
+            <tr>
+                <td><a href="/{}" target="_blank">/{}</a>{}</td>
+                <td class="dest">{}</td>
+                {}<td>{}</td>
+                <td class="row-actions">
+                    <a href="/links/{}/qr" target="_blank" title="{}">QR</a>{}
+                </td>
+            </tr>"#,
+                id, id, badge,
+                destination,
+                owner, time::format_utc(link.created_at),
+                id, html_escape(&short_url(req, &link.id)), manage,
+            )
+        })
+        .collect();
+
+    let table = if rows.is_empty() {
+        r#"<p id="empty" class="muted">No links found.</p>"#.to_string()
+    } else {
+        format!(r#"<table id="links-table">
+            <tr><th>Short link</th><th>Destination</th>{}<th>Created</th><th></th></tr>{}
+        </table>"#, if show_all { "<th>Owner</th>" } else { "" }, rows)
+    };
+
+    let incomplete = if complete {
+        ""
+    } else {
+        r#"<p id="incomplete" class="warning">Some links may be missing: the store holds more keys than one dashboard view scans. The JSON API (<code>GET /api/v1/links</code>) pages through all of them.</p>"#
+    };
+
+    let escaped_q = html_escape(&query.q);
+    let sort = if oldest_first { "oldest" } else { "newest" };
+    let scope = if show_all { "all" } else { "" };
+    let page_link = |p: usize, label: &str| -> Result<String> {
+        let mut url = Url::parse("https://edgelink/links")?;
+        url.query_pairs_mut()
+            .append_pair("q", &query.q)
+            .append_pair("sort", sort)
+            .append_pair("scope", scope)
+            .append_pair("page", &p.to_string());
+        Ok(format!(r#"<a href="/links?{}">{}</a>"#, html_escape(url.query().unwrap_or_default()), label))
+    };
+    let prev = if page > 1 { page_link(page - 1, "← Previous")? } else { String::new() };
+    let next = if page < pages { page_link(page + 1, "Next →")? } else { String::new() };
+
+    let summary = if show_all {
+        format!("{} link(s) across all users", total)
+    } else {
+        format!("{} link(s) created by {}", total, html_escape(&user.email))
+    };
+    let mut nav = Vec::new();
+    if user.can(Permission::CreateLinks) {
+        nav.push(r#"<a href="/create">Create new</a>"#);
+    }
+    if user.can(Permission::ManageAnyLink) {
+        nav.push(if show_all { r#"<a href="/links">Only mine</a>"# } else { r#"<a href="/links?scope=all">All links</a>"# });
+    }
+    nav.push(r#"<a href="/tokens">API tokens</a>"#);
+    let nav = nav.iter().map(|link| format!(" · {}", link)).collect::<String>();
+
+    let html = format!(r#"
+<!DOCTYPE html>
+<html>
+<head>
+    <title>EdgeLink - My Links</title>
+    <style>
+        body {{ font-family: system-ui; max-width: 1000px; margin: 50px auto; padding: 20px; background: #f9f9f9; }}
+        .container {{ background: white; padding: 30px; border-radius: 8px; box-shadow: 0 2px 4px rgba(0,0,0,0.1); }}
+        h1 {{ color: #24292f; margin: 0 0 5px 0; font-size: 24px; }}
+        .muted {{ color: #57606a; font-size: 14px; }}
+        .toolbar {{ display: flex; gap: 10px; margin: 20px 0; }}
+        .toolbar input, .toolbar select {{ padding: 8px 12px; border: 1px solid #d0d7de; border-radius: 4px; font-size: 14px; font-family: system-ui; }}
+        .toolbar input {{ flex: 1; }}
+        .toolbar button {{ padding: 8px 16px; background: #0969da; color: white; border: none; border-radius: 4px; cursor: pointer; font-size: 14px; font-weight: 600; }}
+        table {{ width: 100%; border-collapse: collapse; font-size: 13px; }}
+        th, td {{ text-align: left; padding: 8px; border-bottom: 1px solid #eee; vertical-align: middle; }}
+        td.dest {{ max-width: 380px; overflow: hidden; text-overflow: ellipsis; white-space: nowrap; }}
+        a {{ color: #0969da; text-decoration: none; }}
+        a:hover {{ text-decoration: underline; }}
+        .row-actions {{ display: flex; gap: 12px; align-items: center; white-space: nowrap; }}
+        .row-actions form {{ margin: 0; }}
+        button.link {{ background: none; border: none; padding: 0; cursor: pointer; font-size: 13px; font-family: system-ui; }}
+        button.danger {{ color: #d1242f; }}
+        .badge {{ background: #ffebe9; color: #d1242f; border-radius: 10px; padding: 1px 8px; font-size: 11px; }}
+        .warning {{ background: #fff8c5; border: 1px solid #d4a72c; color: #7d4e00; padding: 12px; border-radius: 4px; font-size: 14px; }}
+        .pager {{ display: flex; justify-content: space-between; margin-top: 20px; font-size: 14px; }}
+    </style>
+</head>
+<body>
+    <div id="container" class="container">
+        <h1 id="title">🔗 {}</h1>
+        <p id="summary" class="muted">{}{}</p>
+        <form id="search-form" class="toolbar" method="GET" action="/links">
+            <input type="hidden" name="scope" value="{}">
+            <input id="q" type="search" name="q" value="{}" placeholder="Search by name or destination">
+            <select id="sort" name="sort">
+                <option value="newest"{}>Newest first</option>
+                <option value="oldest"{}>Oldest first</option>
+            </select>
+            <button id="search-btn" type="submit">Search</button>
+        </form>
+        {}{}
+        <div id="pager" class="pager"><span>{}</span><span class="muted">Page {} of {}</span><span>{}</span></div>
+    </div>
+</body>
+</html>"#,
+        if show_all { "All Links" } else { "My Links" }, summary, nav, scope, escaped_q,
+        if oldest_first { "" } else { " selected" },
+        if oldest_first { " selected" } else { "" },
+        incomplete, table, prev, page, pages, next,
+    );
+
+    Response::from_html(html)
+}
@@ -0,0 +1,43 @@
+use worker::*;
+use super::html_escape;
+use crate::auth::AuthError;
+
+/// Minimal status page shared by the handlers that have nothing to show but an explanation.
+pub fn render(status: u16, label: &str, title: &str, message: &str) -> Result<Response> {
+    let html = format!(r#"
+<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>EdgeLink - {}</title>
+    <style>
+        body {{ font-family: system-ui; max-width: 600px; margin: 50px auto; padding: 20px; background: #f9f9f9; }}
+        .container {{ background: white; padding: 30px; border-radius: 8px; box-shadow: 0 2px 4px rgba(0,0,0,0.1); }}
+        .status {{ color: #57606a; font-size: 13px; font-weight: 600; text-transform: uppercase; letter-spacing: 1px; margin-bottom: 10px; }}
+        h1 {{ color: #24292f; margin: 0 0 15px 0; font-size: 24px; }}
+        p {{ color: #57606a; font-size: 14px; line-height: 1.6; margin: 0; }}
+    </style>
+</head>
+<body>
+    <div id="container" class="container">
+        <div id="status" class="status">{} - {}</div>
+        <h1 id="title">{}</h1>
+        <p id="message">{}</p>
+    </div>
+</body>
+</html>"#, html_escape(title), status, html_escape(label), html_escape(title), html_escape(message));
+
+    Ok(Response::from_html(html)?.with_status(status))
+}
+
+pub fn forbidden(message: &str) -> Result<Response> {
+    render(403, "Forbidden", "Not allowed", message)
+}
+
+pub fn auth_failed(error: &AuthError) -> Result<Response> {
+    match error.status {
+        401 => render(401, "Unauthorized", "Sign-in required", error.message),
+        status => render(status, "Forbidden", "Access denied", error.message),
+    }
+}
@@ -1,9 +1,142 @@
 use worker::*;
-use super::not_found;
+use super::{gone, message, not_found, unlock};
+use crate::allocator::Allocator;
+use crate::device;
+use crate::geo;
+use crate::link::{LinkRecord, Platform, Variant};
+use crate::resolve;
+use crate::split;
+use crate::store;
+use crate::template;
 
-pub async fn get_handler(req: Request, ctx: RouteContext<()>) -> Result<Response> {
-    match ctx.env.kv("edgelink")?.get(ctx.param("id").unwrap()).text().await? {
-        Some(url) => Response::redirect(Url::parse(&url)?),
-        None => not_found::handler(req, ctx).await,
+/// How long a sticky A/B link keeps sending a visitor to the same variant.
+const VARIANT_COOKIE_TTL_SECS: u64 = 30 * 24 * 60 * 60;
+
+fn variant_cookie_name(id: &str) -> String {
+    format!("edgelink_variant_{}", id)
+}
+
+/// Pick the A/B variant for this visit. Sticky links reuse the variant named in the visitor's
+/// cookie while it is still part of the split, and otherwise return the cookie to set.
+fn choose_variant<'a>(req: &Request, id: &str, record: &'a LinkRecord) -> Result<Option<(&'a Variant, Option<String>)>> {
+    if record.flags.sticky {
+        let name = variant_cookie_name(id);
+        let cookies = req.headers().get("cookie")?.unwrap_or_default();
+        let pinned = cookies.split(';')
+            .filter_map(|c| c.trim().split_once('='))
+            .find(|(k, _)| *k == name)
+            .and_then(|(_, key)| record.variants.iter().find(|v| split::key(v) == key));
+        if let Some(variant) = pinned {
+            return Ok(Some((variant, None)));
+        }
+    }
+
+    let Some(variant) = split::pick_random(&record.variants)? else {
+        return Ok(None);
+    };
+    let cookie = record.flags.sticky.then(|| format!(
+        "{}={}; Path=/{}; Max-Age={}; HttpOnly; Secure; SameSite=Lax",
+        variant_cookie_name(id), split::key(variant), id, VARIANT_COOKIE_TTL_SECS,
+    ));
+    Ok(Some((variant, cookie)))
+}
+
+/// Follow a short link. Registered for every method so 307/308 links can forward POSTs and the like.
+pub async fn handler(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let id = ctx.param("id").unwrap().to_string();
+    follow(req, ctx, id, None).await
+}
+
+/// Follow `/<id>/<rest>`, which only prefix and template links answer.
+pub async fn prefix_handler(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let id = ctx.param("id").unwrap().to_string();
+    let rest = ctx.param("rest").unwrap().to_string();
+    follow(req, ctx, id, Some(rest)).await
+}
+
+async fn follow(req: Request, ctx: RouteContext<()>, id: String, rest: Option<String>) -> Result<Response> {
+    let store = store::from_env(&ctx.env)?;
+    let record = match store.get(&id).await? {
+        Some(record) => record,
+        None => return not_found::handler(req, ctx).await,
+    };
+    // A device rule beats a location rule, which beats an A/B split, which beats the link's own destination
+    let user_agent = req.headers().get("user-agent")?.unwrap_or_default();
+    let cf = req.cf();
+    let platform = device::classify(&user_agent);
+    // Link previews, crawlers and HEAD requests must not use up click budgets or skew A/B counts
+    let counted = req.method() != Method::Head && platform != Some(Platform::Bot);
+    let targeted = device::select(&record.devices, platform)
+        .or_else(|| geo::select(&record.geo, cf.and_then(|cf| cf.country()).as_deref(), cf.and_then(|cf| cf.continent()).as_deref()));
+    // The variant is only counted once every check below has passed and the visitor is redirected
+    let mut variant = None;
+    let mut variant_cookie = None;
+    let destination = match targeted {
+        Some(destination) => destination.to_string(),
+        None => match choose_variant(&req, &id, &record)? {
+            Some((chosen, cookie)) => {
+                variant = Some(split::key(chosen));
+                variant_cookie = cookie;
+                chosen.destination.clone()
+            },
+            None => record.destination.clone(),
+        },
+    };
+    let is_template = template::is_template(&destination);
+    if rest.is_some() && !record.flags.prefix && !is_template {
+        return not_found::handler(req, ctx).await;
+    }
+
+    if record.is_removed() {
+        return gone::removed();
+    }
+
+    if record.is_expired(Date::now().as_millis()) {
+        return gone::expired();
+    }
+
+    if record.password.is_some() && !unlock::is_unlocked(&req, &ctx.env, &id, &record)? {
+        return unlock::render_form(&id, &unlock::requested_path(&req)?, None);
+    }
+
+    if let Some(limit) = record.max_clicks {
+        if !counted {
+            return message::render(200, "OK", "Open this link in a browser",
+                "This link can only be opened a limited number of times, so it is not followed for previews or automated requests.");
+        }
+        if !Allocator::from_env(&ctx.env)?.consume(&id, limit).await? {
+            return gone::exhausted();
+        }
+    }
+
+    let url = req.url()?;
+    let mut destination = if is_template {
+        match template::render(&destination, rest.as_deref().unwrap_or_default(), url.query()).and_then(|r| Url::parse(&r).ok()) {
+            Some(rendered) => rendered,
+            None => return not_found::handler(req, ctx).await,
+        }
+    } else {
+        Url::parse(&destination)?
+    };
+    // A template consumes the path itself, so the prefix setting only applies to plain destinations
+    if let Some(rest) = rest.as_deref().filter(|r| !r.is_empty() && !is_template) {
+        destination = match resolve::append_path(destination, rest) {
+            Some(url) => url,
+            None => return not_found::handler(req, ctx).await,
+        };
+    }
+    let target = resolve::apply_query(record.query, destination, url.query());
+    if let Some(variant) = variant.filter(|_| counted) {
+        Allocator::from_env(&ctx.env)?.record_variant(&id, &variant).await?;
+    }
+    match variant_cookie {
+        Some(cookie) => {
+            // Built by hand because `Response::redirect` returns immutable headers
+            let headers = Headers::new();
+            headers.set("Location", target.as_str())?;
+            headers.set("Set-Cookie", &cookie)?;
+            Ok(Response::empty()?.with_status(record.status).with_headers(headers))
+        },
+        None => Response::redirect_with_status(target, record.status),
     }
 }
@@ -0,0 +1,195 @@
+use worker::*;
+use super::{html_escape, message};
+use crate::auth::{self, User};
+use crate::auth::tokens::{ApiToken, Scope, TokenStore, MAX_NAME_LENGTH};
+use crate::expiry;
+use crate::time;
+
+/// The signed-in browser user; tokens can be listed, minted and revoked only from a session.
+async fn session_user(req: &Request, env: &Env) -> Result<std::result::Result<User, Response>> {
+    Ok(match auth::authenticate(req, env).await? {
+        Ok(user) if user.scopes.is_none() => Ok(user),
+        Ok(_) => Err(message::forbidden("API tokens cannot be used to manage API tokens.")?),
+        Err(error) => Err(message::auth_failed(&error)?),
+    })
+}
+
+pub async fn get_handler(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let user = match session_user(&req, &ctx.env).await? {
+        Ok(user) => user,
+        Err(response) => return Ok(response),
+    };
+    let tokens = TokenStore::from_env(&ctx.env)?.list(&user.email).await?;
+    render(&user, &tokens, None, None)
+}
+
+pub async fn post_handler(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let user = match session_user(&req, &ctx.env).await? {
+        Ok(user) => user,
+        Err(response) => return Ok(response),
+    };
+    let store = TokenStore::from_env(&ctx.env)?;
+    let form = req.form_data().await.ok();
+    let field = |name: &str| form.as_ref().and_then(|f| f.get_field(name)).unwrap_or_default();
+
+    let now = Date::now().as_millis();
+    let name = field("name").trim().to_string();
+    let scopes: Vec<Scope> = form.as_ref()
+        .and_then(|f| f.get_all("scope"))
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|entry| match entry {
+            FormEntry::Field(value) => Scope::parse(&value),
+            FormEntry::File(_) => None,
+        })
+        .collect();
+
+    let error = if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+        Some(format!("Name must be 1-{} characters", MAX_NAME_LENGTH))
+    } else if scopes.is_empty() {
+        Some("Pick at least one scope".to_string())
+    } else {
+        None
+    };
+    let expires_at = match field("expires").trim() {
+        "" => Ok(None),
+        value => expiry::parse(value, now).map(Some),
+    };
+    let expires_at = match (error, expires_at) {
+        (None, Ok(expires_at)) => expires_at,
+        (Some(error), _) | (None, Err(error)) => return render(&user, &store.list(&user.email).await?, None, Some(&error)),
+    };
+
+    let (secret, _) = store.mint(&user, &name, scopes, expires_at, now).await?;
+    let mut response = render(&user, &store.list(&user.email).await?, Some(&secret), None)?;
+    // The only time the token is ever shown
+    response.headers_mut().set("Cache-Control", "no-store")?;
+    Ok(response)
+}
+
+pub async fn revoke_handler(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let user = match session_user(&req, &ctx.env).await? {
+        Ok(user) => user,
+        Err(response) => return Ok(response),
+    };
+    let id = ctx.param("id").unwrap().to_string();
+    if !TokenStore::from_env(&ctx.env)?.revoke(&user.email, &id).await? {
+        return message::render(404, "Not Found", "Unknown token", "This token does not exist or has already been revoked.");
+    }
+
+    let mut url = req.url()?;
+    url.set_path("/tokens");
+    Response::redirect_with_status(url, 303)
+}
+
+fn render(user: &User, tokens: &[ApiToken], minted: Option<&str>, error: Option<&str>) -> Result<Response> {
+    let now = Date::now().as_millis();
+    let error_html = error
+        .map(|e| format!(r#"<div id="error-message" class="error">{}</div>"#, html_escape(e)))
+        .unwrap_or_default();
+    let minted_html = minted
+        .map(|secret| format!(r#"<div id="minted" class="notice">Copy your new token now; it will not be shown again.<code id="minted-token">{}</code></div>"#, html_escape(secret)))
+        .unwrap_or_default();
+
+    let rows: String = tokens.iter()
+        .map(|token| {
+            let scopes = token.scopes.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(", ");
+            let expires = match token.expires_at {
+                Some(at) if at <= now => format!(r#"<span class="badge">expired</span> {}"#, time::format_utc(at)),
+                Some(at) => time::format_utc(at),
+                None => "Never".to_string(),
+            };
+            let last_used = token.last_used_at.map_or_else(|| "Never".to_string(), time::format_utc);
+            let id = html_escape(&token.id);
+            format!(r#"
+            <tr>
+                <td>{}</td>
+                <td><code>{}</code></td>
+                <td>{}</td>
+                <td>{}</td>
+                <td>{}</td>
+                <td><form method="POST" action="/tokens/{}/revoke" onsubmit="return confirm('Revoke this token?')"><button type="submit" class="secondary danger">Revoke</button></form></td>
+            </tr>"#,
+                html_escape(&token.name), scopes, time::format_utc(token.created_at), expires, last_used, id,
+            )
+        })
+        .collect();
+    let table = if rows.is_empty() {
+        r#"<p id="empty" class="muted">No tokens yet.</p>"#.to_string()
+    } else {
+        format!(r#"<table id="tokens-table">
+            <tr><th>Name</th><th>Scopes</th><th>Created</th><th>Expires</th><th>Last used</th><th></th></tr>{}
+        </table>"#, rows)
+    };
+
+    let scope_boxes: String = Scope::ALL.iter()
+        .map(|scope| format!(r#"<label><input type="checkbox" name="scope" value="{0}"> {0}</label>"#, scope.as_str()))
+        .collect();
+
+    let html = format!(r#"
+<!DOCTYPE html>
+<html>
+<head>
+    <title>EdgeLink - API Tokens</title>
+    <style>
+        body {{ font-family: system-ui; max-width: 800px; margin: 50px auto; padding: 20px; background: #f9f9f9; }}
+        .container {{ background: white; padding: 30px; border-radius: 8px; box-shadow: 0 2px 4px rgba(0,0,0,0.1); margin-bottom: 20px; }}
+        h1 {{ color: #24292f; margin: 0 0 5px 0; font-size: 24px; }}
+        h2 {{ color: #24292f; margin: 0 0 15px 0; font-size: 18px; }}
+        .field {{ margin-bottom: 20px; }}
+        .field > label {{ display: block; font-weight: 600; color: #333; margin-bottom: 6px; font-size: 14px; }}
+        .scopes {{ display: flex; gap: 20px; font-size: 14px; color: #57606a; }}
+        input[type=text] {{ width: 100%; box-sizing: border-box; padding: 8px 12px; border: 1px solid #d0d7de; border-radius: 4px; font-size: 14px; font-family: system-ui; }}
+        input:focus {{ outline: none; border-color: #0969da; box-shadow: 0 0 0 3px rgba(9,105,218,0.1); }}
+        button {{ width: 100%; padding: 10px; background: #0969da; color: white; border: none; border-radius: 4px; cursor: pointer; font-size: 14px; font-weight: 600; transition: background 0.2s; }}
+        button:hover {{ background: #0860ca; }}
+        button.secondary {{ width: auto; padding: 4px 10px; background: white; color: #24292f; border: 1px solid #d0d7de; font-weight: 500; }}
+        button.secondary:hover {{ background: #f6f8fa; }}
+        button.danger {{ color: #d1242f; border-color: #ff8182; }}
+        table {{ width: 100%; border-collapse: collapse; font-size: 13px; }}
+        th, td {{ text-align: left; padding: 8px; border-bottom: 1px solid #eee; vertical-align: middle; }}
+        td form {{ margin: 0; }}
+        .muted {{ color: #57606a; font-size: 14px; }}
+        .badge {{ background: #ffebe9; color: #d1242f; border-radius: 10px; padding: 1px 8px; font-size: 11px; }}
+        .error {{ background: #ffebe9; border: 1px solid #ff8182; color: #d1242f; padding: 12px; border-radius: 4px; margin-bottom: 20px; font-size: 14px; }}
+        .notice {{ background: #dafbe1; border: 1px solid #4ac26b; color: #1a7f37; padding: 12px; border-radius: 4px; margin-bottom: 20px; font-size: 14px; }}
+        .notice code {{ display: block; margin-top: 8px; font-size: 13px; color: #24292f; word-break: break-all; }}
+        a {{ color: #0969da; text-decoration: none; }}
+        a:hover {{ text-decoration: underline; }}
+    </style>
+</head>
+<body>
+    <div id="container" class="container">
+        <h1 id="title">🔑 API Tokens</h1>
+        <p id="summary" class="muted">Tokens act as {} with your current role · <a href="/links">My links</a></p>
+    </div>
+    <div id="mint" class="container">
+        <h2 id="mint-title">New token</h2>
+        {}{}
+        <form id="mint-form" method="POST" action="/tokens">
+            <div id="name-field" class="field">
+                <label id="name-label" for="name">Name</label>
+                <input id="name" type="text" name="name" maxlength="{}" placeholder="e.g. CI pipeline" required>
+            </div>
+            <div id="scope-field" class="field">
+                <label id="scope-label">Scopes</label>
+                <div class="scopes">{}</div>
+            </div>
+            <div id="expires-field" class="field">
+                <label id="expires-label" for="expires">Expires (optional)</label>
+                <input id="expires" type="text" name="expires" placeholder="90d or 2025-12-31T18:00 (UTC)">
+            </div>
+            <button id="mint-btn" type="submit">Create Token</button>
+        </form>
+    </div>
+    <div id="tokens" class="container">
+        <h2 id="tokens-title">Your tokens</h2>
+        {}
+    </div>
+</body>
+</html>"#,
+        html_escape(&user.email), error_html, minted_html, MAX_NAME_LENGTH, scope_boxes, table,
+    );
+
+    Response::from_html(html)
+}
@@ -0,0 +1,170 @@
+use worker::*;
+use super::html_escape;
+use crate::crypto;
+use crate::link::LinkRecord;
+use crate::store;
+
+/// How long a successful unlock is remembered before the visitor is prompted again.
+const UNLOCK_TTL_SECS: u64 = 60 * 60;
+
+fn cookie_name(id: &str) -> String {
+    format!("edgelink_unlock_{}", id)
+}
+
+/// The signature covers the password salt so changing the password invalidates old cookies.
+fn cookie_message(id: &str, record: &LinkRecord, expires: u64) -> String {
+    let salt = record.password.as_ref().map(|p| p.salt.as_str()).unwrap_or_default();
+    format!("{}|{}|{}", id, expires, salt)
+}
+
+fn cookie_secret(env: &Env) -> Result<String> {
+    Ok(env.secret("COOKIE_SECRET")
+        .map_err(|_| Error::from("COOKIE_SECRET must be set to use password-protected links"))?
+        .to_string())
+}
+
+/// Whether the request carries a valid, unexpired unlock cookie for `id`.
+pub fn is_unlocked(req: &Request, env: &Env, id: &str, record: &LinkRecord) -> Result<bool> {
+    let name = cookie_name(id);
+    let cookies = req.headers().get("cookie")?.unwrap_or_default();
+    let value = cookies.split(';')
+        .filter_map(|c| c.trim().split_once('='))
+        .find(|(k, _)| *k == name)
+        .map(|(_, v)| v.to_string());
+
+    let Some((expires, signature)) = value.as_deref().and_then(|v| v.split_once('.')) else {
+        return Ok(false);
+    };
+    let Ok(expires) = expires.parse::<u64>() else {
+        return Ok(false);
+    };
+    if expires <= Date::now().as_millis() / 1000 {
+        return Ok(false);
+    }
+
+    Ok(crypto::verify_signature(&cookie_secret(env)?, &cookie_message(id, record, expires), signature))
+}
+
+/// Path and query of the request that hit the password form, so unlocking returns to it.
+pub fn requested_path(req: &Request) -> Result<String> {
+    let url = req.url()?;
+    Ok(match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    })
+}
+
+/// Where to send the visitor after unlocking: the path they asked for, including a prefix
+/// remainder and query string, as long as it is still under `/<id>`; otherwise the link itself.
+fn target(id: &str, requested: &str) -> String {
+    let link = format!("/{}", id);
+    match requested.strip_prefix(&link) {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', '?']) => requested.to_string(),
+        _ => link,
+    }
+}
+
+pub fn render_form(id: &str, requested: &str, error: Option<&str>) -> Result<Response> {
+    let error_html = error
+        .map(|e| format!(r#"<div id="error-message" class="error">{}</div>"#, html_escape(e)))
+        .unwrap_or_default();
+
+    let html = format!(r#"
+<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>EdgeLink - Password Required</title>
+    <style>
+        body {{ font-family: system-ui; max-width: 600px; margin: 50px auto; padding: 20px; background: #f9f9f9; }}
+        .container {{ background: white; padding: 30px; border-radius: 8px; box-shadow: 0 2px 4px rgba(0,0,0,0.1); }}
+        h1 {{ color: #24292f; margin: 0 0 25px 0; font-size: 24px; }}
+        .field {{ margin-bottom: 20px; }}
+        .field label {{ display: block; font-weight: 600; color: #333; margin-bottom: 6px; font-size: 14px; }}
+        input {{ width: 100%; box-sizing: border-box; padding: 8px 12px; border: 1px solid #d0d7de; border-radius: 4px; font-size: 14px; font-family: system-ui; }}
+        input:focus {{ outline: none; border-color: #0969da; box-shadow: 0 0 0 3px rgba(9,105,218,0.1); }}
+        button {{ width: 100%; padding: 10px; background: #0969da; color: white; border: none; border-radius: 4px; cursor: pointer; font-size: 14px; font-weight: 600; transition: background 0.2s; }}
+        button:hover {{ background: #0860ca; }}
+        button:active {{ background: #0757ba; }}
+        .error {{ background: #ffebe9; border: 1px solid #ff8182; color: #d1242f; padding: 12px; border-radius: 4px; margin-bottom: 20px; font-size: 14px; }}
+    </style>
+</head>
+<body>
+    <div id="container" class="container">
+        <h1 id="title">🔒 This link is password protected</h1>
+        {}
+        <form id="unlock-form" method="POST" action="/unlock/{}">
+            <input type="hidden" name="to" value="{}">
+            <div id="password-field" class="field">
+                <label id="password-label" for="password">Password</label>
+                <input id="password" type="password" name="password" required autofocus>
+            </div>
+            <button id="submit-btn" type="submit">Continue</button>
+        </form>
+    </div>
+</body>
+</html>"#, error_html, html_escape(id), html_escape(&target(id, requested)));
+
+    Ok(Response::from_html(html)?.with_status(if error.is_some() { 401 } else { 200 }))
+}
+
+pub async fn post_handler(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let id = ctx.param("id").unwrap().to_string();
+    let record = match store::from_env(&ctx.env)?.get(&id).await? {
+        Some(record) => record,
+        None => return Response::error("Not Found", 404),
+    };
+    let form = req.form_data().await.ok();
+    let field = |name: &str| match form.as_ref().and_then(|form| form.get(name)) {
+        Some(worker::FormEntry::Field(value)) => value,
+        _ => String::new(),
+    };
+    let to = target(&id, &field("to"));
+    let Some(password) = record.password.as_ref() else {
+        return redirect_to_link(&req, &to, None);
+    };
+
+    if !password.verify(&field("password")) {
+        return render_form(&id, &to, Some("Incorrect password"));
+    }
+
+    let expires = Date::now().as_millis() / 1000 + UNLOCK_TTL_SECS;
+    let signature = crypto::sign(&cookie_secret(&ctx.env)?, &cookie_message(&id, &record, expires));
+    let cookie = format!(
+        "{}={}.{}; Path=/{}; Max-Age={}; HttpOnly; Secure; SameSite=Lax",
+        cookie_name(&id), expires, signature, id, UNLOCK_TTL_SECS,
+    );
+    redirect_to_link(&req, &to, Some(&cookie))
+}
+
+/// Redirect to `target`, a path on this host from [`target`].
+fn redirect_to_link(req: &Request, target: &str, cookie: Option<&str>) -> Result<Response> {
+    let url = req.url()?.join(target)?;
+    // Built by hand because `Response::redirect` returns immutable headers
+    let headers = Headers::new();
+    headers.set("Location", url.as_str())?;
+    if let Some(cookie) = cookie {
+        headers.set("Set-Cookie", cookie)?;
+    }
+    Ok(Response::empty()?.with_status(303).with_headers(headers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_keeps_paths_under_the_link() {
+        assert_eq!(target("docs", "/docs"), "/docs");
+        assert_eq!(target("docs", "/docs/api/auth?lang=en"), "/docs/api/auth?lang=en");
+        assert_eq!(target("docs", "/docs?ref=mail"), "/docs?ref=mail");
+    }
+
+    #[test]
+    fn target_refuses_anywhere_else() {
+        for requested in ["", "/", "/docsx", "/other/docs", "https://evil.example/docs", "//evil.example", "docs"] {
+            assert_eq!(target("docs", requested), "/docs", "{}", requested);
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use crate::crypto;
+use crate::destination;
+use crate::link::Variant;
+
+const MAX_VARIANTS: usize = 10;
+/// Length of a [`key`]; plenty to tell a link's few variants apart.
+const KEY_LENGTH: usize = 12;
+
+/// Normalize destinations and check the weights: between 2 and 10 variants, each weighted
+/// 1-100, adding up to 100. An empty list turns the split off.
+pub fn validate(variants: Vec<Variant>) -> Result<Vec<Variant>, String> {
+    if variants.is_empty() {
+        return Ok(variants);
+    }
+    if variants.len() < 2 || variants.len() > MAX_VARIANTS {
+        return Err(format!("An A/B split needs 2-{} destinations", MAX_VARIANTS));
+    }
+
+    let mut validated: Vec<Variant> = Vec::with_capacity(variants.len());
+    for variant in variants {
+        if !(1..=100).contains(&variant.weight) {
+            return Err("Each A/B weight must be between 1 and 100".to_string());
+        }
+        let destination = destination::normalize(&variant.destination).map_err(|m| format!("A/B destination: {}", m))?;
+        if validated.iter().any(|v| v.destination == destination) {
+            return Err(format!("{} is in the A/B split more than once", destination));
+        }
+        validated.push(Variant { destination, weight: variant.weight });
+    }
+    let total: u32 = validated.iter().map(|v| u32::from(v.weight)).sum();
+    if total != 100 {
+        return Err(format!("A/B weights must add up to 100, not {}", total));
+    }
+    Ok(validated)
+}
+
+/// Parse variants written one per line as `70 https://example.com/a`; the weight may end in `%`.
+pub fn parse(text: &str) -> Result<Vec<Variant>, String> {
+    let mut variants = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut parts = line.split_whitespace();
+        let (Some(weight), Some(destination), None) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(format!("A/B line {} must be a weight and a URL", number + 1));
+        };
+        let weight = weight.trim_end_matches('%').parse::<u8>()
+            .map_err(|_| format!("A/B line {} must start with a weight between 1 and 100", number + 1))?;
+        variants.push(Variant { destination: destination.to_string(), weight });
+    }
+    validate(variants)
+}
+
+/// The inverse of [`parse`], for pre-filling the edit form.
+pub fn format(variants: &[Variant]) -> String {
+    variants.iter()
+        .map(|v| format!("{} {}", v.weight, v.destination))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Stable, cookie-safe identifier of a variant, derived from its destination so it survives
+/// reweighting and reordering. Used for the pinning cookie and the per-variant click counts.
+pub fn key(variant: &Variant) -> String {
+    crypto::digest(&variant.destination)[..KEY_LENGTH].to_string()
+}
+
+/// The variant that `roll`, uniform in `0..100`, lands on.
+pub fn pick(variants: &[Variant], roll: u8) -> Option<&Variant> {
+    let mut upper = 0u32;
+    variants.iter()
+        .find(|v| {
+            upper += u32::from(v.weight);
+            u32::from(roll) < upper
+        })
+        .or(variants.last())
+}
+
+/// A variant chosen at random according to the weights.
+pub fn pick_random(variants: &[Variant]) -> worker::Result<Option<&Variant>> {
+    let roll = u32::from_le_bytes(crypto::random_bytes::<4>()?) % 100;
+    Ok(pick(variants, roll as u8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variants() -> Vec<Variant> {
+        parse("70 https://example.com/a\n30% example.com/b").unwrap()
+    }
+
+    #[test]
+    fn parse_reads_weights_and_normalizes() {
+        assert_eq!(variants(), vec![
+            Variant { destination: "https://example.com/a".into(), weight: 70 },
+            Variant { destination: "https://example.com/b".into(), weight: 30 },
+        ]);
+        assert_eq!(parse(&format(&variants())).unwrap(), variants());
+        assert_eq!(parse("").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn validate_checks_weights_and_destinations() {
+        assert!(parse("100 https://example.com/a").is_err());
+        assert!(parse("60 https://example.com/a\n30 https://example.com/b").is_err());
+        assert!(parse("0 https://example.com/a\n100 https://example.com/b").is_err());
+        assert!(parse("50 https://example.com/a\n50 example.com/a").is_err());
+        assert!(parse("50 https://example.com/a\n50 not-a-domain").is_err());
+        assert!(parse("half https://example.com/a\n50 https://example.com/b").is_err());
+    }
+
+    #[test]
+    fn pick_follows_weights() {
+        let variants = variants();
+        assert_eq!(pick(&variants, 0).unwrap().weight, 70);
+        assert_eq!(pick(&variants, 69).unwrap().weight, 70);
+        assert_eq!(pick(&variants, 70).unwrap().weight, 30);
+        assert_eq!(pick(&variants, 99).unwrap().weight, 30);
+        let share = (0..100).filter(|&roll| pick(&variants, roll).unwrap().weight == 70).count();
+        assert_eq!(share, 70);
+        assert_eq!(pick(&[], 0), None);
+    }
+
+    #[test]
+    fn key_depends_only_on_destination() {
+        let variants = variants();
+        let reweighted = Variant { weight: 50, ..variants[0].clone() };
+        assert_eq!(key(&variants[0]), key(&reweighted));
+        assert_ne!(key(&variants[0]), key(&variants[1]));
+        assert_eq!(key(&variants[0]).len(), KEY_LENGTH);
+    }
+}
@@ -0,0 +1,73 @@
+use async_trait::async_trait;
+use worker::{Env, Result};
+
+use crate::allocator::Allocator;
+use crate::config;
+use crate::link::LinkRecord;
+
+pub mod d1;
+pub mod kv;
+pub mod memory;
+
+/// Lightweight view of a link used for listings, cheap enough to serve from KV metadata.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkSummary {
+    pub id: String,
+    pub owner: String,
+    pub destination: String,
+    pub created_at: u64,
+    /// Disabled or soft-deleted.
+    pub removed: bool,
+    /// Password-protected; only those who can manage the link may see its destination.
+    pub protected: bool,
+}
+
+impl LinkSummary {
+    pub fn from_record(id: &str, record: &LinkRecord) -> Self {
+        Self {
+            id: id.to_string(),
+            owner: record.owner.clone(),
+            destination: record.destination.clone(),
+            created_at: record.created_at,
+            removed: record.is_removed(),
+            protected: record.password.is_some(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LinkPage {
+    pub links: Vec<LinkSummary>,
+    /// Opaque cursor for the next page, `None` once the listing is complete.
+    pub cursor: Option<String>,
+}
+
+/// Persistence for link records, keyed by short ID.
+#[async_trait(?Send)]
+pub trait LinkStore {
+    async fn get(&self, id: &str) -> Result<Option<LinkRecord>>;
+
+    /// Store `record` under `id` unless the ID is already in use. Returns `false` on conflict.
+    async fn put_if_absent(&self, id: &str, record: &LinkRecord) -> Result<bool>;
+
+    /// Replace an existing record. Returns `false` if `id` does not exist.
+    async fn update(&self, id: &str, record: &LinkRecord) -> Result<bool>;
+
+    /// Remove a record entirely. Returns `false` if `id` does not exist.
+    async fn delete(&self, id: &str) -> Result<bool>;
+
+    async fn list(&self, cursor: Option<String>, limit: usize) -> Result<LinkPage>;
+
+    /// Links that point at `destination`, newest first, optionally only those owned by `owner`.
+    /// May return stale candidates; callers should check the records they get back.
+    async fn find_by_destination(&self, destination: &str, owner: Option<&str>) -> Result<Vec<(String, LinkRecord)>>;
+}
+
+/// Build the store selected by the `LINK_STORE` var (`kv` by default, or `d1`).
+pub fn from_env(env: &Env) -> Result<Box<dyn LinkStore>> {
+    match config::var(env, "LINK_STORE").as_deref() {
+        None | Some("kv") => Ok(Box::new(kv::KvLinkStore::new(env.kv(kv::BINDING)?, Allocator::from_env(env)?))),
+        Some("d1") => Ok(Box::new(d1::D1LinkStore::new(env.d1(d1::BINDING)?))),
+        Some(other) => Err(format!("Unknown LINK_STORE backend '{}'", other).into()),
+    }
+}
@@ -0,0 +1,116 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use worker::wasm_bindgen::JsValue;
+use worker::{D1Database, Result};
+
+use super::{LinkPage, LinkStore, LinkSummary};
+use crate::link::LinkRecord;
+
+pub const BINDING: &str = "edgelink_db";
+
+/// Enough candidates to skip past a few removed links with the same destination.
+const DESTINATION_MATCH_LIMIT: usize = 10;
+
+/// Links stored in a D1 (SQLite) table; see `migrations/0001_links.sql` for the schema.
+pub struct D1LinkStore {
+    db: D1Database,
+}
+
+#[derive(Deserialize)]
+struct ListRow {
+    id: String,
+    record: String,
+}
+
+impl D1LinkStore {
+    pub fn new(db: D1Database) -> Self {
+        Self { db }
+    }
+
+    async fn changes(&self, query: &str, id: &str, record: &LinkRecord) -> Result<usize> {
+        let result = self.db.prepare(query)
+            .bind(&[
+                id.into(),
+                record.to_json()?.into(),
+                record.owner.as_str().into(),
+                record.destination.as_str().into(),
+                (record.created_at as f64).into(),
+            ])?
+            .run()
+            .await?;
+        Ok(result.meta()?.and_then(|m| m.changes).unwrap_or(0))
+    }
+}
+
+#[async_trait(?Send)]
+impl LinkStore for D1LinkStore {
+    async fn get(&self, id: &str) -> Result<Option<LinkRecord>> {
+        let raw: Option<String> = self.db.prepare("SELECT record FROM links WHERE id = ?1")
+            .bind(&[id.into()])?
+            .first(Some("record"))
+            .await?;
+        Ok(raw.and_then(|raw| LinkRecord::from_stored(&raw, None, 0)).map(|(record, _)| record))
+    }
+
+    async fn put_if_absent(&self, id: &str, record: &LinkRecord) -> Result<bool> {
+        let changes = self.changes(
+            "INSERT INTO links (id, record, owner, destination, created_at) VALUES (?1, ?2, ?3, ?4, ?5) \
+             ON CONFLICT(id) DO NOTHING",
+            id,
+            record,
+        ).await?;
+        Ok(changes > 0)
+    }
+
+    async fn update(&self, id: &str, record: &LinkRecord) -> Result<bool> {
+        let changes = self.changes(
+            "UPDATE links SET record = ?2, owner = ?3, destination = ?4, created_at = ?5 WHERE id = ?1",
+            id,
+            record,
+        ).await?;
+        Ok(changes > 0)
+    }
+
+    async fn delete(&self, id: &str) -> Result<bool> {
+        let result = self.db.prepare("DELETE FROM links WHERE id = ?1")
+            .bind(&[id.into()])?
+            .run()
+            .await?;
+        Ok(result.meta()?.and_then(|m| m.changes).unwrap_or(0) > 0)
+    }
+
+    async fn list(&self, cursor: Option<String>, limit: usize) -> Result<LinkPage> {
+        let rows: Vec<ListRow> = self.db
+            .prepare("SELECT id, record FROM links WHERE id > ?1 ORDER BY id LIMIT ?2")
+            .bind(&[cursor.unwrap_or_default().into(), (limit as f64).into()])?
+            .all()
+            .await?
+            .results()?;
+
+        let cursor = if rows.len() == limit { rows.last().map(|row| row.id.clone()) } else { None };
+        let links = rows.iter()
+            .filter_map(|row| {
+                LinkRecord::from_stored(&row.record, None, 0).map(|(record, _)| LinkSummary::from_record(&row.id, &record))
+            })
+            .collect();
+
+        Ok(LinkPage { links, cursor })
+    }
+
+    async fn find_by_destination(&self, destination: &str, owner: Option<&str>) -> Result<Vec<(String, LinkRecord)>> {
+        let rows: Vec<ListRow> = self.db
+            .prepare(
+                "SELECT id, record FROM links WHERE destination = ?1 AND (?2 IS NULL OR owner = ?2 COLLATE NOCASE) \
+                 ORDER BY created_at DESC LIMIT ?3",
+            )
+            // `None.into()` would be `undefined`, which D1 refuses to bind
+            .bind(&[destination.into(), owner.map_or(JsValue::NULL, JsValue::from), (DESTINATION_MATCH_LIMIT as f64).into()])?
+            .all()
+            .await?
+            .results()?;
+
+        Ok(rows.into_iter()
+            .filter_map(|row| LinkRecord::from_stored(&row.record, None, 0).map(|(record, _)| (row.id, record)))
+            .collect())
+    }
+}
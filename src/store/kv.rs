@@ -0,0 +1,170 @@
+use async_trait::async_trait;
+use worker::{kv::KvStore, Date, DateInit, Result};
+
+use super::{LinkPage, LinkStore, LinkSummary};
+use crate::allocator::Allocator;
+use crate::crypto;
+use crate::link::{LinkMetadata, LinkRecord};
+
+pub const BINDING: &str = "edgelink";
+
+/// Expired links are kept this long past their expiry so visitors get the "expired" page
+/// instead of a 404, after which KV deletes them.
+const EXPIRED_RETENTION_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// Destination index: `dest:<digest>` holds the ID most recently created or edited to point there.
+const DESTINATION_PREFIX: &str = "dest:";
+
+/// KV has no conditional writes, so new IDs are first claimed through the [`Allocator`].
+pub struct KvLinkStore {
+    kv: KvStore,
+    allocator: Allocator,
+}
+
+impl KvLinkStore {
+    pub fn new(kv: KvStore, allocator: Allocator) -> Self {
+        Self { kv, allocator }
+    }
+
+    async fn write(&self, id: &str, record: &LinkRecord) -> Result<()> {
+        let mut put = self.kv.put(id, record.to_json()?)?
+            .metadata(record.metadata())?;
+        if let Some(expiration) = kv_expiration(record) {
+            put = put.expiration(expiration);
+        }
+        put.execute().await?;
+        Ok(())
+    }
+
+    /// Point the global and per-owner destination index entries at `id`.
+    async fn index(&self, id: &str, record: &LinkRecord) -> Result<()> {
+        for key in [destination_key(&record.destination, None), destination_key(&record.destination, Some(&record.owner))] {
+            let mut put = self.kv.put(&key, id)?;
+            if let Some(expiration) = kv_expiration(record) {
+                put = put.expiration(expiration);
+            }
+            put.execute().await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait(?Send)]
+impl LinkStore for KvLinkStore {
+    async fn get(&self, id: &str) -> Result<Option<LinkRecord>> {
+        let (raw, metadata) = self.kv.get(id).text_with_metadata::<LinkMetadata>().await?;
+        let Some(raw) = raw else {
+            return Ok(None);
+        };
+
+        match LinkRecord::from_stored(&raw, metadata.as_ref(), legacy_created_at(metadata.as_ref())) {
+            Some((record, stale)) => {
+                // Lazily rewrite legacy plain-string values as JSON records on first read
+                if stale {
+                    self.write(id, &record).await?;
+                }
+                Ok(Some(record))
+            },
+            None => Ok(None),
+        }
+    }
+
+    async fn put_if_absent(&self, id: &str, record: &LinkRecord) -> Result<bool> {
+        if !self.allocator.reserve(id, kv_expiration(record)).await? {
+            return Ok(false);
+        }
+        // Links written before the allocator existed were never reserved
+        if self.kv.get(id).text().await?.is_some() {
+            return Ok(false);
+        }
+        // A failed write must not leave the name reserved, or it stays taken for good
+        if let Err(error) = self.write(id, record).await {
+            self.allocator.release(id).await?;
+            return Err(error);
+        }
+        if let Err(error) = self.index(id, record).await {
+            self.kv.delete(id).await?;
+            self.allocator.release(id).await?;
+            return Err(error);
+        }
+        Ok(true)
+    }
+
+    async fn update(&self, id: &str, record: &LinkRecord) -> Result<bool> {
+        let Some(previous) = self.get(id).await? else {
+            return Ok(false);
+        };
+        self.write(id, record).await?;
+        if previous.destination != record.destination {
+            self.index(id, record).await?;
+        }
+        Ok(true)
+    }
+
+    async fn delete(&self, id: &str) -> Result<bool> {
+        if self.kv.get(id).text().await?.is_none() {
+            return Ok(false);
+        }
+        self.kv.delete(id).await?;
+        self.allocator.release(id).await?;
+        Ok(true)
+    }
+
+    async fn list(&self, cursor: Option<String>, limit: usize) -> Result<LinkPage> {
+        let mut request = self.kv.list().limit(limit as u64);
+        if let Some(cursor) = cursor {
+            request = request.cursor(cursor);
+        }
+        let response = request.execute().await?;
+
+        let links = response.keys.into_iter()
+            // Auxiliary keys use a `prefix:` namespace that short IDs can never contain
+            .filter(|key| !key.name.contains(':'))
+            .map(|key| {
+                let metadata: LinkMetadata = key.metadata
+                    .and_then(|m| serde_json::from_value(m).ok())
+                    .unwrap_or_default();
+                LinkSummary {
+                    created_at: legacy_created_at(Some(&metadata)),
+                    owner: metadata.created_by.unwrap_or_default(),
+                    destination: metadata.destination.unwrap_or_default(),
+                    removed: metadata.removed,
+                    protected: metadata.protected,
+                    id: key.name,
+                }
+            })
+            .collect();
+
+        Ok(LinkPage {
+            links,
+            cursor: if response.list_complete { None } else { response.cursor },
+        })
+    }
+
+    async fn find_by_destination(&self, destination: &str, owner: Option<&str>) -> Result<Vec<(String, LinkRecord)>> {
+        let Some(id) = self.kv.get(&destination_key(destination, owner)).text().await? else {
+            return Ok(Vec::new());
+        };
+        Ok(self.get(&id).await?.map(|record| (id, record)).into_iter().collect())
+    }
+}
+
+fn destination_key(destination: &str, owner: Option<&str>) -> String {
+    match owner {
+        Some(owner) => format!("{}{}", DESTINATION_PREFIX, crypto::digest(format!("{}\n{}", owner.to_lowercase(), destination))),
+        None => format!("{}{}", DESTINATION_PREFIX, crypto::digest(destination)),
+    }
+}
+
+/// When KV should drop the record, in epoch seconds; the reservation is released at the same time.
+fn kv_expiration(record: &LinkRecord) -> Option<u64> {
+    record.expires_at.map(|at| at / 1000 + EXPIRED_RETENTION_SECS)
+}
+
+/// Timestamps in metadata are epoch millis, or a JS date string for links created before records.
+fn legacy_created_at(metadata: Option<&LinkMetadata>) -> u64 {
+    match metadata.and_then(|m| m.created_at.as_deref()) {
+        Some(value) => value.parse().unwrap_or_else(|_| Date::new(DateInit::String(value.to_string())).as_millis()),
+        None => 0,
+    }
+}
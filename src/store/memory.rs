@@ -0,0 +1,154 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+use async_trait::async_trait;
+use worker::Result;
+
+use super::{LinkPage, LinkStore, LinkSummary};
+use crate::link::LinkRecord;
+
+/// In-process store used by native unit tests.
+#[derive(Default)]
+pub struct MemoryLinkStore {
+    links: RefCell<BTreeMap<String, LinkRecord>>,
+}
+
+impl MemoryLinkStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait(?Send)]
+impl LinkStore for MemoryLinkStore {
+    async fn get(&self, id: &str) -> Result<Option<LinkRecord>> {
+        Ok(self.links.borrow().get(id).cloned())
+    }
+
+    async fn put_if_absent(&self, id: &str, record: &LinkRecord) -> Result<bool> {
+        let mut links = self.links.borrow_mut();
+        if links.contains_key(id) {
+            return Ok(false);
+        }
+        links.insert(id.to_string(), record.clone());
+        Ok(true)
+    }
+
+    async fn update(&self, id: &str, record: &LinkRecord) -> Result<bool> {
+        match self.links.borrow_mut().get_mut(id) {
+            Some(existing) => {
+                *existing = record.clone();
+                Ok(true)
+            },
+            None => Ok(false),
+        }
+    }
+
+    async fn delete(&self, id: &str) -> Result<bool> {
+        Ok(self.links.borrow_mut().remove(id).is_some())
+    }
+
+    async fn list(&self, cursor: Option<String>, limit: usize) -> Result<LinkPage> {
+        let links = self.links.borrow();
+        let start = match &cursor {
+            Some(cursor) => Bound::Excluded(cursor.as_str()),
+            None => Bound::Unbounded,
+        };
+
+        let page: Vec<LinkSummary> = links
+            .range::<str, _>((start, Bound::Unbounded))
+            .take(limit)
+            .map(|(id, record)| LinkSummary::from_record(id, record))
+            .collect();
+
+        let cursor = match page.last() {
+            Some(last) if links.range::<str, _>((Bound::Excluded(last.id.as_str()), Bound::Unbounded)).next().is_some() => {
+                Some(last.id.clone())
+            },
+            _ => None,
+        };
+
+        Ok(LinkPage { links: page, cursor })
+    }
+
+    async fn find_by_destination(&self, destination: &str, owner: Option<&str>) -> Result<Vec<(String, LinkRecord)>> {
+        let mut found: Vec<(String, LinkRecord)> = self.links.borrow()
+            .iter()
+            .filter(|(_, record)| record.destination == destination)
+            .filter(|(_, record)| owner.is_none_or(|o| record.owner.eq_ignore_ascii_case(o)))
+            .map(|(id, record)| (id.clone(), record.clone()))
+            .collect();
+        found.sort_by_key(|(_, record)| std::cmp::Reverse(record.created_at));
+        Ok(found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_executor::block_on;
+
+    fn record(destination: &str) -> LinkRecord {
+        LinkRecord::new(destination.to_string(), "owner@example.com".to_string(), 1_700_000_000_000)
+    }
+
+    #[test]
+    fn put_if_absent_rejects_existing_id() {
+        let store = MemoryLinkStore::new();
+        assert!(block_on(store.put_if_absent("docs", &record("https://a.example"))).unwrap());
+        assert!(!block_on(store.put_if_absent("docs", &record("https://b.example"))).unwrap());
+        assert_eq!(block_on(store.get("docs")).unwrap().unwrap().destination, "https://a.example");
+    }
+
+    #[test]
+    fn update_and_delete_require_existing_id() {
+        let store = MemoryLinkStore::new();
+        assert!(!block_on(store.update("docs", &record("https://a.example"))).unwrap());
+        assert!(!block_on(store.delete("docs")).unwrap());
+
+        block_on(store.put_if_absent("docs", &record("https://a.example"))).unwrap();
+        assert!(block_on(store.update("docs", &record("https://b.example"))).unwrap());
+        assert_eq!(block_on(store.get("docs")).unwrap().unwrap().destination, "https://b.example");
+        assert!(block_on(store.delete("docs")).unwrap());
+        assert!(block_on(store.get("docs")).unwrap().is_none());
+    }
+
+    #[test]
+    fn list_pages_through_all_links() {
+        let store = MemoryLinkStore::new();
+        for id in ["a", "b", "c", "d", "e"] {
+            block_on(store.put_if_absent(id, &record("https://example.com"))).unwrap();
+        }
+
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = block_on(store.list(cursor, 2)).unwrap();
+            seen.extend(page.links.into_iter().map(|l| l.id));
+            match page.cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(seen, ["a", "b", "c", "d", "e"]);
+    }
+
+    #[test]
+    fn find_by_destination_filters_by_owner() {
+        let store = MemoryLinkStore::new();
+        block_on(store.put_if_absent("a", &record("https://a.example"))).unwrap();
+        let mut other = record("https://a.example");
+        other.owner = "other@example.com".to_string();
+        other.created_at += 1;
+        block_on(store.put_if_absent("b", &other)).unwrap();
+        block_on(store.put_if_absent("c", &record("https://c.example"))).unwrap();
+
+        let ids = |owner| block_on(store.find_by_destination("https://a.example", owner)).unwrap()
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids(None), ["b", "a"]);
+        assert_eq!(ids(Some("OWNER@example.com")), ["a"]);
+    }
+}
@@ -0,0 +1,167 @@
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use url::form_urlencoded;
+
+/// Everything but RFC 3986 unreserved characters, so a value can never add path segments,
+/// a query or a fragment of its own.
+const COMPONENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+/// One `{...}` in a template destination.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Placeholder<'a> {
+    /// `{1}`, `{2}`, ...: a single path segment after the short name.
+    Segment(usize),
+    /// `{rest}`: every path segment after the short name.
+    Rest,
+    /// `{query.q}`: the visitor's `q` query parameter.
+    Query(&'a str),
+}
+
+impl<'a> Placeholder<'a> {
+    fn parse(token: &'a str) -> Option<Self> {
+        if token == "rest" {
+            return Some(Self::Rest);
+        }
+        if let Some(name) = token.strip_prefix("query.") {
+            return (!name.is_empty()).then_some(Self::Query(name));
+        }
+        match token.parse::<usize>() {
+            Ok(index) if index >= 1 && token.bytes().all(|b| b.is_ascii_digit()) => Some(Self::Segment(index)),
+            _ => None,
+        }
+    }
+}
+
+/// A template split into literal text and placeholders.
+enum Part<'a> {
+    Literal(&'a str),
+    Placeholder(Placeholder<'a>),
+}
+
+fn parse(template: &str) -> Result<Vec<Part<'_>>, &'static str> {
+    let mut parts = Vec::new();
+    let mut remaining = template;
+    while let Some(open) = remaining.find(['{', '}']) {
+        if remaining.as_bytes()[open] == b'}' {
+            return Err("Destination has a '}' without a matching '{'");
+        }
+        let close = remaining[open..].find('}').map(|at| open + at).ok_or("Destination has a '{' without a matching '}'")?;
+        let placeholder = Placeholder::parse(&remaining[open + 1..close])
+            .ok_or("Placeholders must be {1}, {2}, ..., {rest} or {query.name}")?;
+        parts.push(Part::Literal(&remaining[..open]));
+        parts.push(Part::Placeholder(placeholder));
+        remaining = &remaining[close + 1..];
+    }
+    parts.push(Part::Literal(remaining));
+    Ok(parts)
+}
+
+/// Whether `destination` contains placeholders and must be [`render`]ed before redirecting.
+pub fn is_template(destination: &str) -> bool {
+    destination.contains(['{', '}'])
+}
+
+/// Check a template destination when a link is created or edited: every placeholder must be
+/// known and sit after the host, so visitors can fill in the path and query but never choose
+/// where the link goes.
+pub fn validate(destination: &str) -> Result<(), &'static str> {
+    if !is_template(destination) {
+        return Ok(());
+    }
+    parse(destination)?;
+
+    let authority_start = destination.find("://").map_or(0, |at| at + 3);
+    let path_start = destination[authority_start..].find(['/', '?', '#']).map(|at| authority_start + at);
+    match (path_start, destination.find(['{', '}'])) {
+        (Some(path_start), Some(first)) if first > path_start => Ok(()),
+        _ => Err("Placeholders may only appear in the path, query or fragment, not the host"),
+    }
+}
+
+/// Fill `template` from `rest`, the path after the short name, and the visitor's `query`.
+/// Missing values become empty strings. Returns `None` if a path segment is `.` or `..`.
+pub fn render(template: &str, rest: &str, query: Option<&str>) -> Option<String> {
+    let segments: Vec<String> = rest.split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| percent_decode_str(segment).decode_utf8_lossy().into_owned())
+        .collect();
+    if segments.iter().any(|segment| segment == "." || segment == "..") {
+        return None;
+    }
+    let encode = |value: &str| utf8_percent_encode(value, COMPONENT).to_string();
+
+    // Validated when the link was saved, so a parse error here means a record from before
+    // templates existed; send the visitor to it unchanged
+    let Ok(parts) = parse(template) else {
+        return Some(template.to_string());
+    };
+    let mut rendered = String::with_capacity(template.len());
+    for part in parts {
+        match part {
+            Part::Literal(text) => rendered.push_str(text),
+            Part::Placeholder(Placeholder::Segment(index)) => {
+                rendered.push_str(&segments.get(index - 1).map(|s| encode(s)).unwrap_or_default());
+            },
+            Part::Placeholder(Placeholder::Rest) => {
+                rendered.push_str(&segments.iter().map(|s| encode(s)).collect::<Vec<_>>().join("/"));
+            },
+            Part::Placeholder(Placeholder::Query(name)) => {
+                let value = query.and_then(|q| form_urlencoded::parse(q.as_bytes()).find(|(key, _)| key == name));
+                rendered.push_str(&value.map(|(_, v)| encode(&v)).unwrap_or_default());
+            },
+        }
+    }
+    Some(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_accepts_known_placeholders_after_the_host() {
+        assert_eq!(validate("https://jira.example.com/browse/{1}"), Ok(()));
+        assert_eq!(validate("https://github.com/{rest}"), Ok(()));
+        assert_eq!(validate("https://www.google.com/search?q={query.q}"), Ok(()));
+        assert_eq!(validate("https://example.com/{1}/{2}#{query.section}"), Ok(()));
+        assert_eq!(validate("https://example.com/plain"), Ok(()));
+    }
+
+    #[test]
+    fn validate_rejects_bad_placeholders() {
+        assert!(validate("https://example.com/{0}").is_err());
+        assert!(validate("https://example.com/{name}").is_err());
+        assert!(validate("https://example.com/{query.}").is_err());
+        assert!(validate("https://example.com/{1").is_err());
+        assert!(validate("https://example.com/1}").is_err());
+        assert!(validate("https://{1}.example.com/").is_err());
+        assert!(validate("https://example.com{rest}").is_err());
+    }
+
+    #[test]
+    fn render_fills_segments_rest_and_query() {
+        assert_eq!(render("https://jira.example.com/browse/{1}", "ABC-123", None).unwrap(), "https://jira.example.com/browse/ABC-123");
+        assert_eq!(render("https://github.com/{rest}", "org/repo/", None).unwrap(), "https://github.com/org/repo");
+        assert_eq!(render("https://github.com/{2}/{1}", "repo/org", None).unwrap(), "https://github.com/org/repo");
+        assert_eq!(render("https://www.google.com/search?q={query.q}", "", Some("q=rust+lang")).unwrap(), "https://www.google.com/search?q=rust%20lang");
+    }
+
+    #[test]
+    fn render_leaves_missing_values_empty() {
+        assert_eq!(render("https://jira.example.com/browse/{1}", "", None).unwrap(), "https://jira.example.com/browse/");
+        assert_eq!(render("https://example.com/?q={query.q}", "", Some("other=1")).unwrap(), "https://example.com/?q=");
+    }
+
+    #[test]
+    fn render_encodes_values() {
+        assert_eq!(render("https://example.com/{1}", "a%3Fb%23c", None).unwrap(), "https://example.com/a%3Fb%23c");
+        assert_eq!(render("https://example.com/{1}", "a%2Fb", None).unwrap(), "https://example.com/a%2Fb");
+        assert_eq!(render("https://example.com/?q={query.q}", "", Some("q=a%26b%3Dc")).unwrap(), "https://example.com/?q=a%26b%3Dc");
+        assert_eq!(render("https://example.com/wiki/{1}", "caf%C3%A9", None).unwrap(), "https://example.com/wiki/caf%C3%A9");
+    }
+
+    #[test]
+    fn render_rejects_dot_segments() {
+        assert_eq!(render("https://example.com/docs/{rest}", "../admin", None), None);
+        assert_eq!(render("https://example.com/docs/{1}", "%2E%2E", None), None);
+    }
+}